@@ -0,0 +1,45 @@
+use crate::{document::Document, editor::Position, search::Query};
+
+/// Where the editor looks at a document: the cursor, selection, scroll offset and the
+/// highlighted search matches.
+#[derive(Debug, Default, Clone)]
+pub struct View {
+    pub(crate) cursor_position: Position,
+    pub(crate) selection_anchor: Option<Position>,
+    pub(crate) offset: Position,
+    pub(crate) wrap_offset: usize,
+    pub(crate) highlighted_word: Option<Query>,
+}
+
+/// An open document together with the view on it, kept while another one is shown.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    document: Document,
+    view: View,
+}
+
+impl Buffer {
+    #[must_use]
+    #[inline]
+    pub fn new(document: Document, view: View) -> Self {
+        Self { document, view }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn into_parts(self) -> (Document, View) {
+        (self.document, self.view)
+    }
+}
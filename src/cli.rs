@@ -0,0 +1,372 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+use crate::constants::VERSION;
+
+/// A file given on the command line, `line` and `column` count from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileArg {
+    path: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl FileArg {
+    #[must_use]
+    #[inline]
+    pub fn new(path: String, line: Option<usize>, column: Option<usize>) -> Self {
+        Self { path, line, column }
+    }
+
+    /// Read `path[:line[:column]]`. The suffix is only split off when the whole
+    /// argument is not an existing file.
+    #[must_use]
+    pub fn parse(arg: &str) -> Self {
+        if Path::new(arg).exists() {
+            return Self::new(arg.to_string(), None, None);
+        }
+        match split_number(arg) {
+            Some((rest, last)) => match split_number(rest) {
+                Some((path, line)) => Self::new(path.to_string(), Some(line), Some(last)),
+                None => Self::new(rest.to_string(), Some(last), None),
+            },
+            None => Self::new(arg.to_string(), None, None),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+/// What the editor starts with.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Options {
+    files: Vec<FileArg>,
+    read_only: bool,
+    tab_width: Option<usize>,
+    expand_tab: Option<bool>,
+}
+
+impl Options {
+    #[must_use]
+    #[inline]
+    pub fn new(files: Vec<FileArg>, read_only: bool) -> Self {
+        Self {
+            files,
+            read_only,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn files(&self) -> &[FileArg] {
+        &self.files
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// tab width given on the command line, if any
+    #[must_use]
+    #[inline]
+    pub fn tab_width(&self) -> Option<usize> {
+        self.tab_width
+    }
+
+    #[inline]
+    pub fn set_tab_width(&mut self, width: Option<usize>) {
+        self.tab_width = width;
+    }
+
+    /// whether the Tab key inserts spaces, if given on the command line
+    #[must_use]
+    #[inline]
+    pub fn expand_tab(&self) -> Option<bool> {
+        self.expand_tab
+    }
+
+    #[inline]
+    pub fn set_expand_tab(&mut self, expand_tab: Option<bool>) {
+        self.expand_tab = expand_tab;
+    }
+}
+
+/// What the command line asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Edit(Options),
+    Help,
+    Version,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    UnknownOption(String),
+    InvalidLine(String),
+    InvalidTabWidth(String),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "unknown option: {}", option),
+            CliError::InvalidLine(arg) => write!(f, "invalid line number: {}", arg),
+            CliError::InvalidTabWidth(arg) => write!(f, "invalid tab width: {}", arg),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Parse the arguments after the program name. `+N` starts at line `N` of the next
+/// file, or of the last one when no file follows it.
+///
+/// # Errors
+///
+/// fails on an unknown option, a `+N` which is not a number or a tab width which is not
+/// a positive number
+pub fn parse<I>(args: I) -> Result<Command, CliError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut files: Vec<FileArg> = Vec::new();
+    let mut read_only = false;
+    let mut tab_width = None;
+    let mut expand_tab = None;
+    let mut line = None;
+    let mut only_files = false;
+    for arg in args {
+        if only_files {
+            files.push(FileArg::new(arg, line.take(), None));
+            continue;
+        }
+        match arg.as_str() {
+            "--" => only_files = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-R" | "--read-only" => read_only = true,
+            "--expand-tab" => expand_tab = Some(true),
+            "--no-expand-tab" => expand_tab = Some(false),
+            _ if arg.starts_with("--tab-width=") => {
+                let width = arg
+                    .strip_prefix("--tab-width=")
+                    .and_then(|width| width.parse().ok())
+                    .filter(|width| *width > 0)
+                    .ok_or_else(|| CliError::InvalidTabWidth(arg.clone()))?;
+                tab_width = Some(width);
+            }
+            _ if arg.starts_with('+') => {
+                let number = arg
+                    .get(1..)
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(|| CliError::InvalidLine(arg.clone()))?;
+                line = Some(number);
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(CliError::UnknownOption(arg));
+            }
+            _ => {
+                let mut file = FileArg::parse(&arg);
+                if let Some(line) = line.take() {
+                    file.line = Some(line);
+                    file.column = None;
+                }
+                files.push(file);
+            }
+        }
+    }
+    if let (Some(line), Some(file)) = (line, files.last_mut()) {
+        file.line = Some(line);
+        file.column = None;
+    }
+    let mut options = Options::new(files, read_only);
+    options.set_tab_width(tab_width);
+    options.set_expand_tab(expand_tab);
+    Ok(Command::Edit(options))
+}
+
+/// split `text:N` into `text` and `N`, `text` must not be empty
+fn split_number(arg: &str) -> Option<(&str, usize)> {
+    let (rest, number) = arg.rsplit_once(':')?;
+    let number = number.parse().ok()?;
+    (!rest.is_empty()).then_some((rest, number))
+}
+
+#[must_use]
+pub fn usage() -> String {
+    format!(
+        "notepad {}
+
+Usage: notepad [OPTIONS] [+LINE] [FILE[:LINE[:COLUMN]]]...
+
+Every file is opened in its own buffer, a file which does not exist yet is
+created when it is saved.
+
+Options:
+  +LINE            start at LINE of the next file
+  -R, --read-only  open the files read-only
+  --tab-width=N    show tabs N columns wide
+  --expand-tab     make the Tab key insert spaces, the default
+  --no-expand-tab  make the Tab key insert a tab character
+  -h, --help       print this help
+  -V, --version    print the version
+  --               treat the remaining arguments as files
+",
+        VERSION
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{parse, CliError, Command, FileArg, Options};
+
+    fn parse_args(args: &[&str]) -> Result<Command, CliError> {
+        parse(args.iter().map(|arg| (*arg).to_string()))
+    }
+
+    fn file(path: &str, line: Option<usize>, column: Option<usize>) -> FileArg {
+        FileArg::new(path.to_string(), line, column)
+    }
+
+    fn edit(files: Vec<FileArg>, read_only: bool) -> Result<Command, CliError> {
+        Ok(Command::Edit(Options::new(files, read_only)))
+    }
+
+    #[test]
+    fn positions_after_file_names() {
+        assert_eq!(
+            parse_args(&["a.rs:3:7", "b.rs:12", "c.rs"]),
+            edit(
+                vec![
+                    file("a.rs", Some(3), Some(7)),
+                    file("b.rs", Some(12), None),
+                    file("c.rs", None, None),
+                ],
+                false
+            )
+        );
+        // only trailing numbers are positions
+        assert_eq!(
+            parse_args(&["c:\\notes:x", "log:2:"]),
+            edit(
+                vec![file("c:\\notes:x", None, None), file("log:2:", None, None)],
+                false
+            )
+        );
+        assert_eq!(
+            parse_args(&[":5"]),
+            edit(vec![file(":5", None, None)], false)
+        );
+    }
+
+    #[test]
+    fn existing_file_with_a_colon_is_not_split() {
+        let path = env::temp_dir().join(format!("notepad-cli-{}:12", std::process::id()));
+        fs::write(&path, "").expect("a writable temporary directory");
+        let name = path.to_string_lossy().to_string();
+        let parsed = parse_args(&[&name]);
+        fs::remove_file(&path).expect("the temporary file");
+        assert_eq!(parsed, edit(vec![file(&name, None, None)], false));
+    }
+
+    #[test]
+    fn plus_line_applies_to_the_next_or_last_file() {
+        assert_eq!(
+            parse_args(&["+4", "a.rs:1:2", "b.rs", "+9"]),
+            edit(
+                vec![file("a.rs", Some(4), None), file("b.rs", Some(9), None)],
+                false
+            )
+        );
+        assert_eq!(
+            parse_args(&["+0", "a.rs"]),
+            edit(vec![file("a.rs", Some(0), None)], false)
+        );
+        // a line alone opens an empty buffer
+        assert_eq!(parse_args(&["+3"]), edit(Vec::new(), false));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        for arg in ["+", "+x", "+-1", "+99999999999999999999999"] {
+            assert_eq!(
+                parse_args(&[arg, "a.rs"]),
+                Err(CliError::InvalidLine(arg.to_string()))
+            );
+        }
+        // a position too large for a number is part of the file name
+        assert_eq!(
+            parse_args(&["a.rs:99999999999999999999999"]),
+            edit(
+                vec![file("a.rs:99999999999999999999999", None, None)],
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            parse_args(&["-R", "a.rs"]),
+            edit(vec![file("a.rs", None, None)], true)
+        );
+        assert_eq!(parse_args(&["--read-only"]), edit(Vec::new(), true));
+        assert_eq!(parse_args(&["a.rs", "--help"]), Ok(Command::Help));
+        assert_eq!(parse_args(&["-V"]), Ok(Command::Version));
+        assert_eq!(
+            parse_args(&["--", "-R", "+3", "--help"]),
+            edit(
+                vec![
+                    file("-R", None, None),
+                    file("+3", None, None),
+                    file("--help", None, None)
+                ],
+                false
+            )
+        );
+        assert_eq!(parse_args(&["-"]), edit(vec![file("-", None, None)], false));
+        assert_eq!(
+            parse_args(&["-x"]),
+            Err(CliError::UnknownOption("-x".to_string()))
+        );
+    }
+
+    #[test]
+    fn tab_options() {
+        let Ok(Command::Edit(options)) = parse_args(&["--tab-width=8", "--no-expand-tab"]) else {
+            panic!("not an edit command");
+        };
+        assert_eq!(options.tab_width(), Some(8));
+        assert_eq!(options.expand_tab(), Some(false));
+        assert_eq!(parse_args(&[]), edit(Vec::new(), false));
+        for arg in ["--tab-width=0", "--tab-width=", "--tab-width=x"] {
+            assert_eq!(
+                parse_args(&[arg]),
+                Err(CliError::InvalidTabWidth(arg.to_string()))
+            );
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::constants::{FILL_COLUMN, GUTTER_MIN_WIDTH, INDENT_WIDTH, TAB_WIDTH};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    /// distance to the cursor line, the cursor line itself shows its absolute number
+    Relative,
+}
+
+impl LineNumbers {
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    line_numbers: LineNumbers,
+    gutter_min_width: usize,
+    soft_wrap: bool,
+    auto_fill: bool,
+    fill_column: usize,
+    indent_width: usize,
+    tab_width: usize,
+    expand_tab: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            line_numbers: LineNumbers::default(),
+            gutter_min_width: GUTTER_MIN_WIDTH,
+            soft_wrap: false,
+            auto_fill: false,
+            fill_column: FILL_COLUMN,
+            indent_width: INDENT_WIDTH,
+            tab_width: TAB_WIDTH,
+            expand_tab: true,
+        }
+    }
+}
+
+impl Config {
+    #[must_use]
+    #[inline]
+    pub fn line_numbers(&self) -> LineNumbers {
+        self.line_numbers
+    }
+
+    #[inline]
+    pub fn set_line_numbers(&mut self, line_numbers: LineNumbers) {
+        self.line_numbers = line_numbers;
+    }
+
+    /// minimal number of digits of the line number gutter
+    #[must_use]
+    #[inline]
+    pub fn gutter_min_width(&self) -> usize {
+        self.gutter_min_width
+    }
+
+    #[inline]
+    pub fn set_gutter_min_width(&mut self, width: usize) {
+        self.gutter_min_width = width;
+    }
+
+    /// whether long rows are wrapped over several screen lines
+    #[must_use]
+    #[inline]
+    pub fn soft_wrap(&self) -> bool {
+        self.soft_wrap
+    }
+
+    #[inline]
+    pub fn set_soft_wrap(&mut self, soft_wrap: bool) {
+        self.soft_wrap = soft_wrap;
+    }
+
+    /// whether a line break is inserted when typing past the fill column
+    #[must_use]
+    #[inline]
+    pub fn auto_fill(&self) -> bool {
+        self.auto_fill
+    }
+
+    #[inline]
+    pub fn set_auto_fill(&mut self, auto_fill: bool) {
+        self.auto_fill = auto_fill;
+    }
+
+    /// column used by auto fill and paragraph reflow
+    #[must_use]
+    #[inline]
+    pub fn fill_column(&self) -> usize {
+        self.fill_column
+    }
+
+    #[inline]
+    pub fn set_fill_column(&mut self, column: usize) {
+        self.fill_column = column;
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn indent_width(&self) -> usize {
+        self.indent_width
+    }
+
+    #[inline]
+    pub fn set_indent_width(&mut self, width: usize) {
+        self.indent_width = width;
+    }
+
+    /// number of columns between two tab stops
+    #[must_use]
+    #[inline]
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    #[inline]
+    pub fn set_tab_width(&mut self, width: usize) {
+        self.tab_width = width.max(1);
+    }
+
+    /// whether the Tab key inserts spaces instead of a tab character
+    #[must_use]
+    #[inline]
+    pub fn expand_tab(&self) -> bool {
+        self.expand_tab
+    }
+
+    #[inline]
+    pub fn set_expand_tab(&mut self, expand_tab: bool) {
+        self.expand_tab = expand_tab;
+    }
+
+    /// text inserted for one level of indentation
+    #[must_use]
+    pub fn indent_unit(&self) -> String {
+        if self.expand_tab {
+            " ".repeat(self.indent_width)
+        } else {
+            String::from("\t")
+        }
+    }
+}
@@ -12,6 +12,12 @@ pub const STATUS_BG_COLOR: Color = Color::Rgb {
     g: 239,
     b: 239,
 };
+/// status bar of the panes without the focus
+pub const INACTIVE_STATUS_BG_COLOR: Color = Color::Rgb {
+    r: 160,
+    g: 160,
+    b: 160,
+};
 
 pub const NUMBER_COLOR: Color = Color::Rgb {
     r: 220,
@@ -55,6 +61,48 @@ pub const SECONDARY_KEYWORDS_COLOR: Color = Color::Rgb {
     b: 152,
 };
 
+pub const HEADING_COLOR: Color = Color::Rgb {
+    r: 203,
+    g: 75,
+    b: 22,
+};
+
+pub const EMPHASIS_COLOR: Color = Color::Rgb {
+    r: 220,
+    g: 50,
+    b: 47,
+};
+
+pub const LIFETIME_COLOR: Color = Color::Rgb {
+    r: 238,
+    g: 130,
+    b: 98,
+};
+
+pub const MACRO_COLOR: Color = Color::Rgb {
+    r: 102,
+    g: 153,
+    b: 204,
+};
+
+pub const ATTRIBUTE_COLOR: Color = Color::Rgb {
+    r: 147,
+    g: 161,
+    b: 161,
+};
+
+pub const ESCAPE_COLOR: Color = Color::Rgb {
+    r: 255,
+    g: 184,
+    b: 108,
+};
+
+pub const LINE_NUMBER_COLOR: Color = Color::Rgb {
+    r: 131,
+    g: 148,
+    b: 150,
+};
+
 pub const NONE_COLOR: Color = Color::Rgb {
     r: 255,
     g: 255,
@@ -62,3 +110,14 @@ pub const NONE_COLOR: Color = Color::Rgb {
 };
 
 pub const QUIT_TIMES: u8 = 2;
+
+pub const GUTTER_MIN_WIDTH: usize = 3;
+
+pub const FILL_COLUMN: usize = 72;
+
+pub const INDENT_WIDTH: usize = 4;
+
+pub const TAB_WIDTH: usize = 4;
+
+/// number of rows at the start and at the end of a file searched for modelines
+pub const MODELINE_ROWS: usize = 5;
@@ -1,38 +1,60 @@
 use std::{
     cmp, fs,
     io::{self, Error, Write},
-    ops::Index,
+    ops::{Index, Range},
 };
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::{
+    constants::MODELINE_ROWS,
     editor::{Position, SearchDirection},
     filetype::FileType,
+    highlighting::is_blank,
+    history::{Edit, History},
+    rope::LineRope,
     row::Row,
+    search::Query,
 };
 
 #[derive(Debug, Default)]
 pub struct Document {
     filename: Option<String>,
-    rows: Vec<Row>,
+    rows: LineRope,
     filetype: FileType,
-    dirty: bool,
+    /// whether the file type was picked by the user, which a new file name keeps
+    filetype_set: bool,
+    history: History,
+    /// refuse edits and saving
+    read_only: bool,
+    /// rows before it are highlighted from the right lexer state
+    stale_from: usize,
+    /// whether the last highlighting marked search matches
+    highlighted_matches: bool,
 }
 
 impl Document {
     #[inline]
     pub fn open(filename: &str) -> Result<Self, Error> {
         let contents = fs::read_to_string(filename)?;
-        let filetype = FileType::from(filename);
-        let mut rows = Vec::new();
-        for value in contents.lines() {
-            rows.push(Row::from(value));
-        }
-        Ok(Self {
+        let rows = contents.lines().map(Row::from).collect();
+        let mut document = Self {
             filename: Some(filename.to_string()),
             rows,
-            filetype,
-            dirty: false,
-        })
+            ..Self::default()
+        };
+        document.detect_filetype();
+        Ok(document)
+    }
+
+    /// an empty document which is created as `filename` when it is saved
+    #[must_use]
+    pub fn new_file(filename: &str) -> Self {
+        Self {
+            filename: Some(filename.to_string()),
+            filetype: FileType::from(filename),
+            ..Self::default()
+        }
     }
 
     #[must_use]
@@ -47,10 +69,38 @@ impl Document {
         self.rows.get(index).map(Row::len).unwrap_or_default()
     }
 
+    /// whether `at` is in the leading whitespace of a row with text, where Tab indents
+    /// the row rather than inserting at the cursor
+    #[must_use]
+    pub fn is_in_indentation(&self, at: &Position) -> bool {
+        self.rows.get(at.y()).is_some_and(|row| {
+            let indent = row.indentation();
+            indent.len() < row.content().len() && at.x() <= indent.graphemes(true).count()
+        })
+    }
+
+    /// text between `start` and `end`, rows are joined by `\n`
+    #[must_use]
+    pub fn text_range(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        for y in start.y()..=cmp::min(end.y(), self.len().saturating_sub(1)) {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            if y > start.y() {
+                text.push('\n');
+            }
+            let from = if y == start.y() { start.x() } else { 0 };
+            let to = if y == end.y() { end.x() } else { row.len() };
+            text.push_str(row.substring(from, to));
+        }
+        text
+    }
+
     #[must_use]
     #[inline]
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.history.is_dirty()
     }
 
     #[must_use]
@@ -74,6 +124,9 @@ impl Document {
     #[inline]
     pub fn set_filename(&mut self, filename: Option<String>) {
         self.filename = filename;
+        if !self.filetype_set {
+            self.detect_filetype();
+        }
     }
 
     #[must_use]
@@ -81,84 +134,581 @@ impl Document {
     pub fn filetype(&self) -> String {
         self.filetype.name()
     }
+
+    /// use `filetype` instead of the detected one
+    pub fn set_filetype(&mut self, filetype: FileType) {
+        self.filetype = filetype;
+        self.filetype_set = true;
+        self.unhighlight_rows();
+    }
+
+    /// detect the file type from the file name and the first and last rows, which may
+    /// hold a shebang or modelines
+    fn detect_filetype(&mut self) {
+        let len = self.rows.len();
+        let lines: Vec<&str> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                *index < MODELINE_ROWS || *index >= len.saturating_sub(MODELINE_ROWS)
+            })
+            .map(|(_, row)| row.content())
+            .collect();
+        self.filetype = FileType::detect(self.filename.as_deref(), &lines);
+        self.unhighlight_rows();
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    #[inline]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
 }
 
 impl Document {
-    pub fn new_line(&mut self, at: &Position) {
-        if at.y() > self.len() {
-            return;
+    /// split the row at `at` and indent the new row like the current one,
+    /// return the cursor position on the new row
+    pub fn new_line(&mut self, at: &Position, indent_unit: &str) -> Position {
+        let text = self.line_break(at, indent_unit);
+        self.insert_recorded(at, &text)
+    }
+
+    /// text inserted by `new_line`: a line break followed by the indentation of the row,
+    /// one more level after an opening brace
+    #[must_use]
+    pub fn line_break(&self, at: &Position, indent_unit: &str) -> String {
+        let mut text = String::from("\n");
+        if let Some(row) = self.rows.get(at.y()) {
+            let before = row.substring(0, at.x());
+            let indentation = row.indentation();
+            text.push_str(indentation.get(..before.len()).unwrap_or(indentation));
+            if self.filetype.uses_braces() && before.trim_end().ends_with('{') {
+                text.push_str(indent_unit);
+            }
         }
+        text
+    }
 
-        self.dirty = true;
-        if at.y() == self.len() {
-            // add new line to the end of the document
-            self.rows.push(Row::default());
-        } else {
-            // split current line to two part and create a new line
-            #[allow(clippy::indexing_slicing)]
-            let current_row = &mut self.rows[at.y()];
-            let next_row = current_row.split(at.x());
-            #[allow(clippy::arithmetic_side_effects)]
-            self.rows.insert(at.y() + 1, next_row);
+    /// Remove one level of indentation before a `}` typed at `at` as the first non blank
+    /// character of the row. Return the cursor position after the brace.
+    pub fn dedent_closing_brace(&mut self, at: &Position, indent_unit: &str) -> Position {
+        if !self.filetype.uses_braces() {
+            return at.clone();
+        }
+        let Some(row) = self.rows.get(at.y()) else {
+            return at.clone();
+        };
+        let before = row.substring(0, at.x());
+        if !before.ends_with('}') || !before.trim_end_matches('}').trim().is_empty() {
+            return at.clone();
+        }
+        let removed = dedent_width(row.indentation(), indent_unit);
+        if removed == 0 {
+            return at.clone();
         }
-        self.unhighlight_rows(at.y());
+        let indent = row.indentation().graphemes(true).count();
+        self.remove(
+            &Position::new(indent.saturating_sub(removed), at.y()),
+            &Position::new(indent, at.y()),
+        );
+        Position::new(at.x().saturating_sub(removed), at.y())
+    }
+
+    /// indent the non empty rows `first..=last` by `indent_unit` as a single change
+    pub fn indent_lines(&mut self, first: usize, last: usize, indent_unit: &str) {
+        let mut edits = Vec::new();
+        for y in first..=cmp::min(last, self.len().saturating_sub(1)) {
+            if self.row_length(y) > 0 {
+                let at = Position::new(0, y);
+                self.insert_text(&at, indent_unit);
+                edits.push(Edit::Insert {
+                    at,
+                    text: indent_unit.to_string(),
+                });
+            }
+        }
+        let at = Position::new(0, first);
+        self.history.record_group(edits, at.clone(), at);
+    }
+
+    /// remove one level of indentation from the rows `first..=last` as a single change
+    pub fn dedent_lines(&mut self, first: usize, last: usize, indent_unit: &str) {
+        let mut edits = Vec::new();
+        for y in first..=cmp::min(last, self.len().saturating_sub(1)) {
+            let Some(row) = self.rows.get(y) else {
+                break;
+            };
+            let indentation = row.indentation();
+            let removed = if indentation.starts_with('\t') {
+                1
+            } else {
+                let spaces = indentation
+                    .len()
+                    .saturating_sub(indentation.trim_start_matches(' ').len());
+                cmp::min(spaces, indent_unit.len().max(1))
+            };
+            if removed > 0 {
+                let at = Position::new(0, y);
+                let text = self.delete_text(&at, &Position::new(removed, y));
+                edits.push(Edit::Delete { at, text });
+            }
+        }
+        let at = Position::new(0, first);
+        self.history.record_group(edits, at.clone(), at);
     }
 
     pub fn insert(&mut self, at: &Position, c: char) {
-        if at.y() > self.len() {
+        self.insert_recorded(at, c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// insert a possibly multi-line `text` as a single change,
+    /// return the position right after it
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        self.insert_recorded(at, text)
+    }
+
+    pub fn delete(&mut self, at: &Position) {
+        if at.y() >= self.len() {
             return;
         }
 
-        self.dirty = true;
-        if at.y() == self.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row);
+        let row_length = self.row_length(at.y());
+        let end = if at.x() < row_length {
+            Position::new(at.x().saturating_add(1), at.y())
+        } else if at.y().saturating_add(1) < self.len() {
+            Position::new(0, at.y().saturating_add(1))
         } else {
-            #[allow(clippy::indexing_slicing)]
-            let row = &mut self.rows[at.y()];
-            row.insert(at.x(), c);
+            return;
+        };
+        let at = Position::new(cmp::min(at.x(), row_length), at.y());
+        let byte = self.byte_position(&at);
+        let text = self.delete_text(&at, &end);
+        self.history
+            .record(Edit::Delete { at: byte, text }, at.clone(), at);
+    }
+
+    /// remove the text between `start` and `end` as a single change
+    pub fn remove(&mut self, start: &Position, end: &Position) {
+        self.replace(start, end, "");
+    }
+
+    /// replace the text between `start` and `end` by `text` as a single change,
+    /// return the position right after the inserted text
+    pub fn replace(&mut self, start: &Position, end: &Position, text: &str) -> Position {
+        if start.y() >= self.len() {
+            return self.insert_recorded(start, text);
         }
-        self.unhighlight_rows(at.y());
+
+        let (start, end) = (self.clamp(start), self.clamp_to_end(end));
+        let mut edits = Vec::new();
+        let after = self.replace_text(&start, &end, text, &mut edits);
+        self.history.record_group(edits, start, after.clone());
+        after
     }
 
-    pub fn delete(&mut self, at: &Position) {
-        if at.y() >= self.len() {
+    /// Replace every match of `query` from `from` up to `until` by `text` as a single
+    /// change, wrapping around the end of the document when `until` is not after `from`.
+    /// Return the number of replacements and the position right after the last one.
+    pub fn replace_all(
+        &mut self,
+        query: &Query,
+        text: &str,
+        from: &Position,
+        until: &Position,
+    ) -> (usize, Position) {
+        let mut edits = Vec::new();
+        let wraps = until <= from;
+        let (mut count, mut after) =
+            self.replace_matches(query, text, from, (!wraps).then_some(until), &mut edits);
+        if wraps {
+            // the replacements after `from` did not move the rows before it
+            let (wrapped, wrapped_after) =
+                self.replace_matches(query, text, &Position::default(), Some(until), &mut edits);
+            count = count.saturating_add(wrapped);
+            after = wrapped_after.or(after);
+        }
+        let after = after.unwrap_or_else(|| from.clone());
+        self.history
+            .record_group(edits, from.clone(), after.clone());
+        (count, after)
+    }
+
+    /// replace the matches starting in `start..end`, up to the end of the document without
+    /// `end`, the edits doing so are added to `edits`
+    fn replace_matches(
+        &mut self,
+        query: &Query,
+        text: &str,
+        start: &Position,
+        end: Option<&Position>,
+        edits: &mut Vec<Edit>,
+    ) -> (usize, Option<Position>) {
+        let mut count: usize = 0;
+        let mut after = None;
+        // rows added by line breaks in `text`, `start` and `end` are positions before them
+        let mut added: usize = 0;
+        let mut y = start.y();
+        while end.is_none_or(|end| y <= end.y()) {
+            let Some(row) = self.rows.get(y.saturating_add(added)) else {
+                break;
+            };
+            let matches: Vec<Range<usize>> = row
+                .matches(query)
+                .filter(|found| y != start.y() || found.start >= start.x())
+                .filter(|found| end.is_none_or(|end| y < end.y() || found.start < end.x()))
+                .collect();
+            // earlier replacements in the row move a match by as much as they moved
+            // the end of the previous match
+            let (mut origin, mut last_end) = (Position::new(0, y.saturating_add(added)), 0);
+            for found in matches {
+                let x = origin
+                    .x()
+                    .saturating_add(found.start.saturating_sub(last_end));
+                let start = Position::new(x, origin.y());
+                let end = Position::new(x.saturating_add(found.len()), origin.y());
+                origin = self.replace_text(&start, &end, text, edits);
+                last_end = found.end;
+                after = Some(origin.clone());
+                count = count.saturating_add(1);
+            }
+            added = origin.y().saturating_sub(y);
+            y = y.saturating_add(1);
+        }
+        (count, after)
+    }
+
+    /// position of the grapheme after `at`, the start of the next row at the end of a row
+    #[must_use]
+    pub fn next_position(&self, at: &Position) -> Position {
+        if at.x() < self.row_length(at.y()) {
+            Position::new(at.x().saturating_add(1), at.y())
+        } else {
+            Position::new(0, at.y().saturating_add(1))
+        }
+    }
+
+    /// Break the row of `at` at its last whitespace which starts before `column` once the
+    /// row is wider than `column`, and indent the new row like the broken one. Return the
+    /// cursor position after the break.
+    pub fn fill_line(&mut self, at: &Position, column: usize, tab_width: usize) -> Position {
+        let Some(row) = self.rows.get(at.y()) else {
+            return at.clone();
+        };
+        if row.column(row.len(), tab_width) <= column {
+            return at.clone();
+        }
+
+        let indentation = row.indentation().to_string();
+        let indent = indentation.graphemes(true).count();
+        let mut space = None;
+        let mut index = indent;
+        while index < cmp::min(at.x(), row.len()) && row.column(index, tab_width) <= column {
+            let start = index;
+            while index < row.len() && row.grapheme(index).is_some_and(is_blank) {
+                index = index.saturating_add(1);
+            }
+            if index > start && start > indent {
+                space = Some((start, index));
+            }
+            index = index.saturating_add(1);
+        }
+        let Some((start, end)) = space else {
+            return at.clone();
+        };
+        if at.x() < end {
+            return at.clone();
+        }
+        self.replace(
+            &Position::new(start, at.y()),
+            &Position::new(end, at.y()),
+            &format!("\n{indentation}"),
+        );
+        Position::new(
+            at.x().saturating_sub(end).saturating_add(indent),
+            at.y().saturating_add(1),
+        )
+    }
+
+    /// Re-wrap the paragraph around `at`, the rows between blank rows, to `column`
+    /// screen columns. Return the cursor position after the same word.
+    pub fn reflow(&mut self, at: &Position, column: usize, tab_width: usize) -> Position {
+        let is_blank_row = |y: usize| {
+            self.rows
+                .get(y)
+                .is_none_or(|row| row.content().trim().is_empty())
+        };
+        if is_blank_row(at.y()) {
+            return at.clone();
+        }
+        let mut first = at.y();
+        while first > 0 && !is_blank_row(first.saturating_sub(1)) {
+            first = first.saturating_sub(1);
+        }
+        let mut last = at.y();
+        while !is_blank_row(last.saturating_add(1)) {
+            last = last.saturating_add(1);
+        }
+
+        let start = Position::new(0, first);
+        let end = Position::new(self.row_length(last), last);
+        let old_text = self.text_range(&start, &end);
+        let indent = self
+            .rows
+            .get(first)
+            .map(Row::indentation)
+            .unwrap_or_default()
+            .to_string();
+        let mut lines = Vec::new();
+        let mut line = indent.clone();
+        for word in old_text.split_whitespace() {
+            if line.len() > indent.len() {
+                let joined = Row::from(format!("{line} {word}"));
+                if joined.column(joined.len(), tab_width) > column {
+                    lines.push(line);
+                    line = indent.clone();
+                } else {
+                    line.push(' ');
+                }
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+        let new_text = lines.join("\n");
+        if new_text == old_text {
+            return at.clone();
+        }
+
+        // number of non blank graphemes before the cursor, which stay the same
+        let before_cursor = self.text_range(&start, at);
+        let mut words_before = before_cursor
+            .graphemes(true)
+            .filter(|g| !is_blank(g))
+            .count();
+        self.replace(&start, &end, &new_text);
+        let mut cursor = start;
+        for grapheme in new_text.graphemes(true) {
+            if words_before == 0 {
+                break;
+            }
+            if grapheme == "\n" {
+                cursor = Position::new(0, cursor.y().saturating_add(1));
+                continue;
+            }
+            if !is_blank(grapheme) {
+                words_before = words_before.saturating_sub(1);
+            }
+            cursor.set_x(cursor.x().saturating_add(1));
+        }
+        cursor
+    }
+
+    /// revert the last change, return the cursor position before it
+    pub fn undo(&mut self) -> Option<Position> {
+        let change = self.history.undo()?;
+        for edit in change.edits().iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        Some(change.before().clone())
+    }
+
+    /// reapply the last undone change, return the cursor position after it
+    pub fn redo(&mut self) -> Option<Position> {
+        let change = self.history.redo()?;
+        for edit in change.edits() {
+            self.apply(edit);
+        }
+        Some(change.after().clone())
+    }
+
+    fn insert_recorded(&mut self, at: &Position, text: &str) -> Position {
+        if at.y() > self.len() || text.is_empty() {
+            return at.clone();
+        }
+
+        let (at, text) = self.past_end(at, text);
+        let byte = self.byte_position(&at);
+        let end = self.insert_text(&at, &text);
+        self.history
+            .record(Edit::Insert { at: byte, text }, at, end.clone());
+        end
+    }
+
+    /// replace the text between `start` and `end` by `text` without recording it,
+    /// the edits doing so are added to `edits`
+    fn replace_text(
+        &mut self,
+        start: &Position,
+        end: &Position,
+        text: &str,
+        edits: &mut Vec<Edit>,
+    ) -> Position {
+        let byte = self.byte_position(start);
+        let removed = self.delete_text(start, end);
+        if !removed.is_empty() {
+            edits.push(Edit::Delete {
+                at: byte.clone(),
+                text: removed,
+            });
+        }
+        if text.is_empty() {
+            return start.clone();
+        }
+        edits.push(Edit::Insert {
+            at: byte,
+            text: text.to_string(),
+        });
+        self.insert_text(start, text)
+    }
+
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { at, text } => self.splice(at, at, text),
+            Edit::Delete { at, .. } => self.splice(at, &edit.end(), ""),
+        }
+    }
+
+    /// replace the text between the byte positions `start` and `end` by `text`
+    fn splice(&mut self, start: &Position, end: &Position, text: &str) {
+        if start.y() == self.len() {
+            self.rows.push(Row::default());
+        }
+        let (Some(first), Some(last)) = (self.rows.get(start.y()), self.rows.get(end.y())) else {
             return;
+        };
+        let (Some(head), Some(tail)) = (
+            first.content().get(..start.x()),
+            last.content().get(end.x()..),
+        ) else {
+            return;
+        };
+        let content = format!("{head}{text}{tail}");
+        self.rows.remove_range(start.y(), end.y().saturating_add(1));
+        for (y, line) in (start.y()..).zip(content.split('\n')) {
+            let mut row = Row::from(line);
+            row.set_modified();
+            self.rows.insert(y, row);
+        }
+        self.mark_stale(start.y());
+    }
+
+    /// `at` with the byte offset of its grapheme in the row as `x`, which is how edits
+    /// are positioned
+    fn byte_position(&self, at: &Position) -> Position {
+        let x = self
+            .rows
+            .get(at.y())
+            .map_or(0, |row| row.substring(0, at.x()).len());
+        Position::new(x, at.y())
+    }
+
+    /// Where and what to insert for `text` typed at `at`. On the row after the last one
+    /// the text goes to the end of the last row behind a line break, so that undo also
+    /// removes the row it adds. A line break typed there is that row itself.
+    fn past_end(&self, at: &Position, text: &str) -> (Position, String) {
+        let Some(last) = self.len().checked_sub(1).filter(|_| at.y() == self.len()) else {
+            return (self.clamp(at), text.to_string());
+        };
+        let at = Position::new(self.row_length(last), last);
+        if text.starts_with('\n') {
+            (at, text.to_string())
+        } else {
+            (at, format!("\n{text}"))
         }
+    }
 
-        self.dirty = true;
-        #[allow(clippy::arithmetic_side_effects)]
-        if at.x() == self.row_length(at.y()) && at.y() + 1 < self.len() {
-            #[allow(clippy::arithmetic_side_effects)]
-            let next_row = self.rows.remove(at.y() + 1);
-            #[allow(clippy::indexing_slicing)]
-            let row = &mut self.rows[at.y()];
-            row.append(&next_row);
+    fn clamp(&self, at: &Position) -> Position {
+        Position::new(cmp::min(at.x(), self.row_length(at.y())), at.y())
+    }
+
+    /// clamp `at` to the last position of the document
+    fn clamp_to_end(&self, at: &Position) -> Position {
+        if at.y() < self.len() {
+            self.clamp(at)
         } else {
-            #[allow(clippy::indexing_slicing)]
-            let row = &mut self.rows[at.y()];
-            row.delete(at.x());
+            let y = self.len().saturating_sub(1);
+            Position::new(self.row_length(y), y)
         }
-        self.unhighlight_rows(at.y());
+    }
+
+    /// insert `text` at `at`, return the position right after it
+    fn insert_text(&mut self, at: &Position, text: &str) -> Position {
+        if at.y() > self.len() {
+            return at.clone();
+        }
+        if at.y() == self.len() {
+            self.rows.push(Row::default());
+        }
+
+        let mut lines = text.split('\n');
+        #[allow(clippy::indexing_slicing)]
+        let current_row = &mut self.rows[at.y()];
+        let tail = current_row.split(at.x());
+        current_row.insert_str(at.x(), lines.next().unwrap_or_default());
+        let mut y = at.y();
+        for line in lines {
+            y = y.saturating_add(1);
+            self.rows.insert(y, Row::from(line));
+        }
+        #[allow(clippy::indexing_slicing)]
+        let last_row = &mut self.rows[y];
+        let x = last_row.len();
+        last_row.append(&tail);
+        self.mark_stale(at.y());
+        Position::new(x, y)
+    }
+
+    /// remove the text between `start` and `end`, return the removed text
+    fn delete_text(&mut self, start: &Position, end: &Position) -> String {
+        if start.y() >= self.len() || end.y() >= self.len() || end.y() < start.y() {
+            return String::new();
+        }
+
+        let mut removed = String::new();
+        #[allow(clippy::indexing_slicing)]
+        if start.y() == end.y() {
+            removed = self.rows[start.y()].delete_range(start.x(), end.x());
+        } else {
+            let tail = self.rows[end.y()].split(end.x());
+            let first = &mut self.rows[start.y()];
+            let first_tail = first.split(start.x());
+            removed.push_str(first_tail.content());
+            #[allow(clippy::arithmetic_side_effects)]
+            for row in self.rows.remove_range(start.y() + 1, end.y() + 1) {
+                removed.push('\n');
+                removed.push_str(row.content());
+            }
+            self.rows[start.y()].append(&tail);
+        }
+        self.mark_stale(start.y());
+        removed
     }
 
     pub fn save(&mut self) -> Result<(), io::Error> {
         #[allow(clippy::pattern_type_mismatch)]
         if let Some(filename) = &self.filename {
             let mut file = fs::File::create(filename)?;
-            self.filetype = FileType::from(filename);
-            for row in &mut self.rows {
+            for row in self.rows.iter() {
                 file.write_all(row.as_bytes())?;
                 file.write_all(b"\n")?;
             }
-            self.dirty = false;
+            self.history.mark_saved();
         }
         Ok(())
     }
 
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
-        if at.y() >= self.len() || query.is_empty() {
+    pub fn find(
+        &self,
+        query: &Query,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<Range<Position>> {
+        if at.y() >= self.len() || query.pattern().is_empty() {
             return None;
         }
 
@@ -166,16 +716,14 @@ impl Document {
             SearchDirection::Forward => (at.y(), self.len()),
             SearchDirection::Backward => (0, at.y().saturating_add(1)),
         };
-        #[allow(clippy::arithmetic_side_effects)]
-        let mut row_iter: Box<dyn DoubleEndedIterator<Item = (usize, &Row)>> =
-            Box::new(self.rows.iter().enumerate().skip(start).take(end - start));
+        let mut row_iter: Box<dyn Iterator<Item = usize>> = Box::new(start..end);
         if direction == SearchDirection::Backward {
-            row_iter = Box::new(row_iter.rev());
+            row_iter = Box::new((start..end).rev());
         }
         let mut x = at.x();
-        for (y, row) in row_iter {
-            if let Some(x) = row.find(query, x, direction) {
-                return Some(Position::new(x, y));
+        for (y, row) in row_iter.filter_map(|y| Some((y, self.rows.get(y)?))) {
+            if let Some(found) = row.find(query, x, direction) {
+                return Some(Position::new(found.start, y)..Position::new(found.end, y));
             }
             x = match direction {
                 SearchDirection::Forward => 0,
@@ -185,25 +733,100 @@ impl Document {
         None
     }
 
-    pub fn highlight(&mut self, word: Option<&String>, until: Option<usize>) {
-        let mut start_with_comment = false;
+    /// like `find`, but continue from the other end of the document when the search
+    /// reaches its end, the flag tells whether it did
+    pub fn find_wrapping(
+        &self,
+        query: &Query,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<(Range<Position>, bool)> {
+        if let Some(found) = self.find(query, at, direction) {
+            return Some((found, false));
+        }
+        let from = match direction {
+            SearchDirection::Forward => Position::default(),
+            SearchDirection::Backward => {
+                let y = self.len().saturating_sub(1);
+                Position::new(self.row_length(y), y)
+            }
+        };
+        self.find(query, &from, direction)
+            .map(|found| (found, true))
+    }
+
+    /// number of matches of `query` which start at or before `at`, and in the whole document
+    #[must_use]
+    pub fn match_count(&self, query: &Query, at: &Position) -> (usize, usize) {
+        let (mut before, mut total) = (0_usize, 0_usize);
+        for (y, row) in self.rows.iter().enumerate() {
+            for found in row.matches(query) {
+                if Position::new(found.start, y) <= *at {
+                    before = before.saturating_add(1);
+                }
+                total = total.saturating_add(1);
+            }
+        }
+        (before, total)
+    }
+
+    /// Highlight the rows up to `until`, from the first one which may be stale. A row is
+    /// only lexed again when its content or the state it starts in changed, so the work
+    /// after an edit stops at the first row which ends in the same state as before.
+    pub fn highlight(&mut self, word: Option<&Query>, until: Option<usize>) {
         let until = until.map_or(self.len(), |times| {
             cmp::min(times.saturating_add(1), self.len())
         });
-        for row in &mut self.rows[..until] {
-            start_with_comment = row.highlight(
-                self.filetype.highlightling_options(),
-                word,
-                start_with_comment,
-            );
+        // matches are marked on every row and cleared from every row
+        let restart = word.is_some() || self.highlighted_matches;
+        let start = if restart {
+            0
+        } else {
+            cmp::min(self.stale_from, until)
+        };
+        let mut state = start
+            .checked_sub(1)
+            .and_then(|previous| self.rows.get(previous))
+            .map(|row| row.end_state().clone())
+            .unwrap_or_default();
+        for row in self.rows.iter_mut().take(until).skip(start) {
+            state = row
+                .highlight(self.filetype.highlightling_options(), word, &state)
+                .clone();
         }
+        // rows after `until` may still show matches, which they lose once they are
+        // highlighted again
+        self.stale_from = if restart {
+            until
+        } else {
+            cmp::max(self.stale_from, until)
+        };
+        self.highlighted_matches = word.is_some();
     }
 
-    fn unhighlight_rows(&mut self, start: usize) {
-        let start = start.saturating_sub(1);
-        for row in self.rows.iter_mut().skip(start) {
+    /// the rows from `start` on may start in another lexer state
+    fn mark_stale(&mut self, start: usize) {
+        self.stale_from = cmp::min(self.stale_from, start);
+    }
+
+    /// highlight every row again, such as after the file type changed
+    fn unhighlight_rows(&mut self) {
+        for row in self.rows.iter_mut() {
             row.reset_highlighted();
         }
+        self.stale_from = 0;
+    }
+}
+
+/// number of graphemes of `indentation` which make up its last indentation level
+fn dedent_width(indentation: &str, indent_unit: &str) -> usize {
+    if indentation.ends_with('\t') {
+        1
+    } else {
+        let spaces = indentation
+            .len()
+            .saturating_sub(indentation.trim_end_matches(' ').len());
+        cmp::min(spaces, indent_unit.len().max(1))
     }
 }
 
@@ -214,3 +837,272 @@ impl Index<usize> for Document {
         &self.rows[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use crate::{
+        editor::Position,
+        filetype::FileType,
+        highlighting::HighlightType,
+        row::Row,
+        search::{Query, SearchOptions},
+    };
+
+    fn document(lines: &[&str]) -> Document {
+        Document {
+            rows: lines.iter().map(|line| Row::from(*line)).collect(),
+            ..Document::default()
+        }
+    }
+
+    fn query(pattern: &str) -> Query {
+        let mut options = SearchOptions::default();
+        options.toggle_regex();
+        Query::new(pattern, options).expect("a valid pattern")
+    }
+
+    fn lines(document: &Document) -> Vec<&str> {
+        document.rows.iter().map(Row::content).collect()
+    }
+
+    #[test]
+    fn new_line_past_the_end_adds_one_row() {
+        let mut document = document(&["one", "two"]);
+        document.new_line(&Position::new(0, 2), "    ");
+        assert_eq!(lines(&document), ["one", "two", ""]);
+
+        assert!(document.undo().is_some());
+        assert_eq!(lines(&document), ["one", "two"]);
+        assert!(!document.is_dirty());
+        assert!(document.redo().is_some());
+        assert_eq!(lines(&document), ["one", "two", ""]);
+    }
+
+    #[test]
+    fn typing_past_the_end_undoes_exactly() {
+        let mut document = document(&["one"]);
+        document.insert(&Position::new(0, 1), 'a');
+        document.insert(&Position::new(1, 1), 'b');
+        assert_eq!(lines(&document), ["one", "ab"]);
+
+        assert_eq!(document.undo(), Some(Position::new(3, 0)));
+        assert_eq!(lines(&document), ["one"]);
+        assert!(!document.is_dirty());
+        assert_eq!(document.redo(), Some(Position::new(2, 1)));
+        assert_eq!(lines(&document), ["one", "ab"]);
+    }
+
+    #[test]
+    fn tab_indents_in_the_leading_whitespace() {
+        let document = document(&["    let x = 1;", "x"]);
+        assert!(document.is_in_indentation(&Position::new(0, 0)));
+        assert!(document.is_in_indentation(&Position::new(4, 0)));
+        assert!(document.is_in_indentation(&Position::new(0, 1)));
+    }
+
+    #[test]
+    fn tab_inserts_after_the_indentation_and_in_rows_without_text() {
+        let document = document(&["    let x = 1;", "", "  "]);
+        assert!(!document.is_in_indentation(&Position::new(5, 0)));
+        assert!(!document.is_in_indentation(&Position::new(14, 0)));
+        assert!(!document.is_in_indentation(&Position::new(0, 1)));
+        assert!(!document.is_in_indentation(&Position::new(2, 2)));
+        assert!(!document.is_in_indentation(&Position::new(0, 3)));
+    }
+
+    #[test]
+    fn reflow_measures_tabs() {
+        let mut eight = document(&["\t\tone two three four"]);
+        eight.reflow(&Position::new(0, 0), 20, 8);
+        assert_eq!(
+            lines(&eight),
+            ["\t\tone", "\t\ttwo", "\t\tthree", "\t\tfour"]
+        );
+        let mut two = document(&["\t\tone two three four"]);
+        two.reflow(&Position::new(0, 0), 20, 2);
+        assert_eq!(lines(&two), ["\t\tone two three", "\t\tfour"]);
+    }
+
+    #[test]
+    fn reflow_measures_wide_characters() {
+        let mut document = document(&["你好 世界 你好 世界"]);
+        let cursor = document.reflow(&Position::new(8, 0), 12, 4);
+        assert_eq!(lines(&document), ["你好 世界", "你好 世界"]);
+        assert_eq!(cursor, Position::new(2, 1));
+    }
+
+    #[test]
+    fn fill_line_keeps_the_indentation() {
+        let mut document = document(&["    one two three"]);
+        let cursor = document.fill_line(&Position::new(17, 0), 12, 4);
+        assert_eq!(lines(&document), ["    one two", "    three"]);
+        assert_eq!(cursor, Position::new(9, 1));
+    }
+
+    #[test]
+    fn fill_line_counts_display_columns() {
+        // the tab and the wide characters make the row wider than its grapheme count
+        let mut document = document(&["\t界界 ab cd"]);
+        assert_eq!(
+            document.fill_line(&Position::new(9, 0), 14, 4),
+            Position::new(9, 0)
+        );
+        let cursor = document.fill_line(&Position::new(9, 0), 12, 4);
+        assert_eq!(lines(&document), ["\t界界 ab", "\tcd"]);
+        assert_eq!(cursor, Position::new(3, 1));
+    }
+
+    #[test]
+    fn replace_all_wraps_around_to_the_start() {
+        let mut document = document(&["a b a", "b a", "a"]);
+        let query = query("a");
+        let (count, after) =
+            document.replace_all(&query, "xy", &Position::new(2, 1), &Position::new(2, 1));
+        assert_eq!(count, 4);
+        assert_eq!(lines(&document), ["xy b xy", "b xy", "xy"]);
+        // the last replacement is the one before `from`
+        assert_eq!(after, Position::new(7, 0));
+
+        // a single undo step restores the whole document
+        assert!(document.undo().is_some());
+        assert_eq!(lines(&document), ["a b a", "b a", "a"]);
+    }
+
+    #[test]
+    fn replace_all_stops_before_until() {
+        let mut document = document(&["a a", "a a", "a a"]);
+        let (count, _) = document.replace_all(
+            &query("a"),
+            "bb",
+            &Position::new(2, 0),
+            &Position::new(2, 1),
+        );
+        assert_eq!(count, 2);
+        assert_eq!(lines(&document), ["a bb", "bb a", "a a"]);
+    }
+
+    #[test]
+    fn replace_all_skips_empty_matches() {
+        let mut document = document(&["xxab", "", "ax"]);
+        let (count, _) = document.replace_all(
+            &query("x*"),
+            "-",
+            &Position::default(),
+            &Position::default(),
+        );
+        assert_eq!(count, 2);
+        assert_eq!(lines(&document), ["-ab", "", "a-"]);
+    }
+
+    #[test]
+    fn save_as_detects_the_filetype_unless_one_was_picked() {
+        let mut detected = document(&["fn main() {}"]);
+        detected.set_filename(Some("main.rs".to_string()));
+        assert_eq!(detected.filetype(), "Rust");
+
+        let mut picked = document(&["fn main() {}"]);
+        picked.set_filetype(FileType::named("Python").expect("a Python syntax"));
+        picked.set_filename(Some("main.rs".to_string()));
+        assert_eq!(picked.filetype(), "Python");
+    }
+
+    fn types(document: &Document, y: usize) -> Vec<HighlightType> {
+        document
+            .row(y)
+            .map(|row| row.highlighting().to_vec())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn matches_are_cleared_below_the_viewport() {
+        let mut lines = vec!["let x = 1;"; 300];
+        lines[100] = "let needle = 1;";
+        lines[250] = "let needle = 2;";
+        let mut document = document(&lines);
+        let query = query("needle");
+        // a search jumping down highlights up to the match it found
+        document.highlight(Some(&query), Some(260));
+        assert!(types(&document, 250).contains(&HighlightType::Match));
+        // after it only the viewport at the top is highlighted
+        document.highlight(None, Some(20));
+        document.highlight(None, Some(110));
+        assert!(!types(&document, 100).contains(&HighlightType::Match));
+        document.highlight(None, None);
+        assert!(!types(&document, 250).contains(&HighlightType::Match));
+    }
+
+    #[test]
+    fn edits_carry_the_lexer_state_to_the_next_rows() {
+        let mut document = document(&["fn a() {}", "let b = 1;", "fn c() {}"]);
+        document.filetype = FileType::from("main.rs");
+        document.highlight(None, None);
+        assert_eq!(types(&document, 2)[0], HighlightType::PrimaryKeywords);
+
+        document.insert_str(&Position::new(0, 1), "/*");
+        document.highlight(None, None);
+        assert_eq!(
+            types(&document, 2),
+            vec![HighlightType::MultilineComment; 9]
+        );
+
+        assert!(document.undo().is_some());
+        document.highlight(None, None);
+        assert_eq!(types(&document, 2)[0], HighlightType::PrimaryKeywords);
+    }
+
+    #[test]
+    fn highlighting_stops_where_the_state_matches() {
+        let mut document = document(&["fn a() {}", "let b = 1;", "fn c() {}"]);
+        document.filetype = FileType::from("main.rs");
+        document.highlight(None, None);
+        // a file type which is not applied to the rows shows which rows are lexed again
+        document.filetype = FileType::default();
+        document.insert(&Position::new(4, 1), 'x');
+        document.highlight(None, None);
+        assert_eq!(types(&document, 1)[0], HighlightType::None);
+        assert_eq!(types(&document, 2)[0], HighlightType::PrimaryKeywords);
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let original = ["fn main() {", "    let x = 1;", "}"];
+        let mut document = document(&original);
+        document.insert_str(&Position::new(14, 1), "\n    let y = 2;");
+        document.remove(&Position::new(4, 1), &Position::new(4, 2));
+        document.delete(&Position::new(11, 0));
+        document.replace(&Position::new(0, 0), &Position::new(2, 0), "pub fn");
+        let edited: Vec<String> = lines(&document).into_iter().map(String::from).collect();
+
+        while document.undo().is_some() {}
+        assert_eq!(lines(&document), original);
+        assert!(!document.is_dirty());
+        while document.redo().is_some() {}
+        assert_eq!(lines(&document), edited);
+        assert!(document.is_dirty());
+    }
+
+    #[test]
+    fn undo_removes_a_combining_mark_merged_with_the_grapheme_before() {
+        let mut document = document(&["y"]);
+        document.insert(&Position::new(1, 0), '\u{306}');
+        assert_eq!(lines(&document), ["y\u{306}"]);
+        document.undo();
+        assert_eq!(lines(&document), ["y"]);
+        assert!(!document.is_dirty());
+        document.redo();
+        assert_eq!(lines(&document), ["y\u{306}"]);
+    }
+
+    #[test]
+    fn undo_restores_a_line_break_before_a_combining_mark() {
+        let mut document = document(&["a", "\u{301}b"]);
+        document.delete(&Position::new(1, 0));
+        assert_eq!(lines(&document), ["a\u{301}b"]);
+        document.undo();
+        assert_eq!(lines(&document), ["a", "\u{301}b"]);
+        assert!(!document.is_dirty());
+        document.redo();
+        assert_eq!(lines(&document), ["a\u{301}b"]);
+    }
+}
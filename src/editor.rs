@@ -1,23 +1,37 @@
 use std::{
-    cmp, env,
+    cmp,
+    collections::HashMap,
     fmt::Display,
-    io,
+    io, mem,
     time::{Duration, Instant},
 };
 
 use crossterm::{
-    event::{KeyCode, KeyEvent, KeyModifiers},
+    event::{Event, KeyCode, KeyEvent, KeyModifiers},
     style::{self, Stylize},
 };
 
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 use crate::{
-    constants::{QUIT_TIMES, STATUS_BG_COLOR, STATUS_FG_COLOR, VERSION},
+    buffer::{Buffer, View},
+    cli::{FileArg, Options},
+    config::{Config, LineNumbers},
+    constants::{
+        INACTIVE_STATUS_BG_COLOR, LINE_NUMBER_COLOR, QUIT_TIMES, STATUS_BG_COLOR, STATUS_FG_COLOR,
+        VERSION,
+    },
     document::Document,
+    filetype::{self, FileType},
+    layout::{Layout, Pane, Rect, SplitDirection},
+    prompt::{self, Completion, PromptHistory, PromptKind, PromptLine},
     row::Row,
+    search::{Query, SearchOptions},
     terminal::Terminal,
 };
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Position {
     x: usize,
     y: usize,
@@ -59,6 +73,18 @@ impl Position {
     }
 }
 
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
 #[derive(Debug)]
 struct StatusMessage {
     time: Instant,
@@ -87,40 +113,42 @@ pub enum SearchDirection {
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
+    config: Config,
     cursor_position: Position,
+    selection_anchor: Option<Position>,
     offset: Position,
+    /// first visible screen line of the row `offset.y` when soft wrap is on
+    wrap_offset: usize,
     document: Document,
     status_message: StatusMessage,
     quit_times: u8,
-    highlighted_word: Option<String>,
+    /// key whose presses `quit_times` counts down
+    discard_key: Option<&'static str>,
+    /// open every file read-only, from `-R`
+    read_only: bool,
+    highlighted_word: Option<Query>,
+    search_options: SearchOptions,
+    /// match counter shown in the search prompt
+    search_status: String,
+    clipboard: String,
+    prompt_history: HashMap<PromptKind, PromptHistory>,
+    /// message bar column of the cursor while a prompt is open
+    prompt_cursor: Option<usize>,
+    /// every open buffer, the slot of the shown one is empty while its document and
+    /// view live in the fields above
+    buffers: Vec<Buffer>,
+    current_buffer: usize,
+    /// every pane, the view of the focused one lives in the fields above
+    panes: Vec<Pane>,
+    current_pane: usize,
+    layout: Layout,
+    /// screen area of the focused pane, its status bar included
+    area: Rect,
 }
 
 impl Default for Editor {
     fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut initial_status =
-            String::from("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Q = quit");
-        let mut document = Document::default();
-        if let Some(filename) = args.get(1) {
-            match Document::open(filename) {
-                Ok(doc) => document = doc,
-                Err(e) => {
-                    initial_status = format!("ERR: Could not open file: {}", filename);
-                    eprintln!("{}", e);
-                }
-            }
-        };
-
-        Self {
-            should_quit: false,
-            terminal: Terminal::default(),
-            cursor_position: Position::default(),
-            offset: Position::default(),
-            document,
-            status_message: StatusMessage::from(initial_status),
-            quit_times: QUIT_TIMES,
-            highlighted_word: None,
-        }
+        Self::with_options(&Options::default())
     }
 }
 
@@ -133,6 +161,76 @@ impl Editor {
         Editor::default()
     }
 
+    /// Open every file of `options` in its own buffer, the first one is shown. A file
+    /// which does not exist yet becomes an empty buffer with its name.
+    #[must_use]
+    pub fn with_options(options: &Options) -> Editor {
+        let mut status = String::from(
+            "HELP: Ctrl-O = open | Ctrl-F = find | Ctrl-R = replace | Ctrl-S = save | Ctrl-Z/Ctrl-Y = undo/redo | Ctrl-Q = quit",
+        );
+        if let Some(error) = filetype::syntax_errors().first() {
+            status = format!("ERR: Could not load syntax definition: {}", error);
+        }
+        let mut buffers = Vec::new();
+        for file in options.files() {
+            let mut document = match Document::open(file.path()) {
+                Ok(document) => document,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    Document::new_file(file.path())
+                }
+                Err(err) => {
+                    status = format!("ERR: Could not open file: {}: {}", file.path(), err);
+                    continue;
+                }
+            };
+            document.set_read_only(options.read_only());
+            let view = View {
+                cursor_position: start_position(&document, file),
+                ..View::default()
+            };
+            buffers.push(Buffer::new(document, view));
+        }
+        if buffers.is_empty() {
+            buffers.push(Buffer::default());
+        }
+        let mut config = Config::default();
+        if let Some(width) = options.tab_width() {
+            config.set_tab_width(width);
+        }
+        if let Some(expand_tab) = options.expand_tab() {
+            config.set_expand_tab(expand_tab);
+        }
+
+        let mut editor = Self {
+            should_quit: false,
+            terminal: Terminal::default(),
+            config,
+            cursor_position: Position::default(),
+            selection_anchor: None,
+            offset: Position::default(),
+            wrap_offset: 0,
+            document: Document::default(),
+            status_message: StatusMessage::from(status),
+            quit_times: QUIT_TIMES,
+            discard_key: None,
+            read_only: options.read_only(),
+            highlighted_word: None,
+            search_options: SearchOptions::default(),
+            search_status: String::new(),
+            clipboard: String::new(),
+            prompt_history: HashMap::new(),
+            prompt_cursor: None,
+            buffers,
+            current_buffer: 0,
+            panes: vec![Pane::default()],
+            current_pane: 0,
+            layout: Layout::default(),
+            area: Rect::default(),
+        };
+        editor.load_buffer(0);
+        editor
+    }
+
     #[inline]
     #[must_use]
     #[allow(clippy::as_conversions)]
@@ -154,6 +252,51 @@ impl Editor {
     pub fn terminal_height(&self) -> usize {
         self.terminal.height() as usize
     }
+
+    /// width of the line number gutter including its separator, 0 if it is hidden
+    #[must_use]
+    pub fn gutter_width(&self) -> usize {
+        if self.config.line_numbers() == LineNumbers::Off {
+            return 0;
+        }
+        let digits = self.document.len().max(1).ilog10().saturating_add(1);
+        let digits = usize::try_from(digits).unwrap_or_default();
+        cmp::max(digits, self.config.gutter_min_width()).saturating_add(1)
+    }
+
+    /// number of columns of the focused pane left for the text after the gutter
+    #[must_use]
+    pub fn text_width(&self) -> usize {
+        self.area.width().saturating_sub(self.gutter_width())
+    }
+
+    /// number of text rows of the focused pane
+    #[must_use]
+    pub fn view_height(&self) -> usize {
+        self.area.height().saturating_sub(1)
+    }
+
+    /// area shared by the panes, every row but the message bar
+    #[must_use]
+    pub fn screen_area(&self) -> Rect {
+        Rect::new(
+            0,
+            0,
+            self.terminal_width(),
+            self.terminal_height().saturating_add(1),
+        )
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    #[inline]
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
 }
 
 impl Editor {
@@ -175,15 +318,28 @@ impl Editor {
                 break;
             }
 
-            let key = Terminal::read_key()?;
-            self.process_keypress(key);
+            match Terminal::read_event()? {
+                Event::Key(key) => self.process_keypress(key),
+                Event::Paste(text) => self.process_paste(&text),
+                _ => (),
+            }
         }
 
         Ok(())
     }
 
     fn process_keypress(&mut self, key: KeyEvent) {
+        if self.document.is_read_only() && is_edit(&key) {
+            self.reset_quit();
+            self.status_message = StatusMessage::from("Buffer is read-only");
+            return;
+        }
         match key.code {
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                if key.modifiers.contains(KeyModifiers::ALT) =>
+            {
+                self.focus_direction(key.code);
+            }
             KeyCode::Up
             | KeyCode::Down
             | KeyCode::Left
@@ -191,33 +347,92 @@ impl Editor {
             | KeyCode::PageUp
             | KeyCode::PageDown
             | KeyCode::Home
-            | KeyCode::End => self.move_cursor(key.code),
+            | KeyCode::End => {
+                if !key.modifiers.contains(KeyModifiers::SHIFT) {
+                    self.selection_anchor = None;
+                } else if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position.clone());
+                }
+                self.move_cursor(key.code);
+                if self.selection_anchor.as_ref() == Some(&self.cursor_position) {
+                    self.selection_anchor = None;
+                }
+            }
+            KeyCode::Esc => self.selection_anchor = None,
+            KeyCode::Enter if self.selection_range().is_some() => {
+                if let Some((start, _)) = self.selection_range() {
+                    let text = self.document.line_break(&start, &self.config.indent_unit());
+                    self.replace_selection(&text);
+                }
+            }
+            KeyCode::Delete | KeyCode::Backspace if self.selection_range().is_some() => {
+                self.replace_selection("");
+            }
             KeyCode::Enter => {
-                self.document.new_line(&self.cursor_position);
-                self.move_cursor(KeyCode::Right);
+                self.cursor_position = self
+                    .document
+                    .new_line(&self.cursor_position, &self.config.indent_unit());
+            }
+            KeyCode::Tab
+                if self.selection_range().is_some()
+                    || self.document.is_in_indentation(&self.cursor_position) =>
+            {
+                self.indent(true);
             }
+            KeyCode::Tab => self.insert_tab(),
+            KeyCode::BackTab => self.indent(false),
             KeyCode::Delete => self.document.delete(&self.cursor_position),
-            KeyCode::Backspace => {
-                if !self.cursor_position.at_beginning() {
-                    self.move_cursor(KeyCode::Left);
-                    self.document.delete(&self.cursor_position);
-                }
+            KeyCode::Backspace if !self.cursor_position.at_beginning() => {
+                self.move_cursor(KeyCode::Left);
+                self.document.delete(&self.cursor_position);
             }
             KeyCode::Char(c) => {
                 if key.modifiers.contains(KeyModifiers::CONTROL) {
                     match c {
                         'f' => self.search(),
+                        'r' => self.replace(),
+                        'o' => self.open(),
+                        'w' if !self.try_close() => return,
                         's' => self.save(),
-                        'q' => {
-                            if !self.try_quit() {
-                                return;
-                            }
-                        }
+                        'c' => self.copy(),
+                        'x' => self.cut(),
+                        'v' => self.paste(),
+                        'z' => self.undo(),
+                        'y' => self.redo(),
+                        'l' => self.toggle_line_numbers(),
+                        't' => self.choose_filetype(),
+                        'q' if !self.try_quit() => return,
                         _ => (),
                     }
-                } else {
+                } else if key.modifiers.contains(KeyModifiers::ALT) {
+                    match c {
+                        'z' => self.toggle_soft_wrap(),
+                        'f' => self.toggle_auto_fill(),
+                        'q' => self.reflow(),
+                        '.' => self.cycle_buffer(true),
+                        ',' => self.cycle_buffer(false),
+                        'b' => self.list_buffers(),
+                        's' => self.split_pane(SplitDirection::Horizontal),
+                        'v' => self.split_pane(SplitDirection::Vertical),
+                        'c' => self.close_pane(),
+                        _ => (),
+                    }
+                } else if !self.replace_selection(c.encode_utf8(&mut [0; 4])) {
                     self.document.insert(&self.cursor_position, c);
                     self.move_cursor(KeyCode::Right);
+                    if c == '}' {
+                        self.cursor_position = self.document.dedent_closing_brace(
+                            &self.cursor_position,
+                            &self.config.indent_unit(),
+                        );
+                    }
+                    if self.config.auto_fill() {
+                        self.cursor_position = self.document.fill_line(
+                            &self.cursor_position,
+                            self.config.fill_column(),
+                            self.config.tab_width(),
+                        );
+                    }
                 }
             }
             _ => (),
@@ -226,36 +441,227 @@ impl Editor {
         self.scroll();
     }
 
+    /// insert pasted text verbatim as a single change
+    fn process_paste(&mut self, text: &str) {
+        if self.document.is_read_only() {
+            self.reset_quit();
+            self.status_message = StatusMessage::from("Buffer is read-only");
+            return;
+        }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.insert_str(&text);
+        self.reset_quit();
+        self.scroll();
+    }
+
     /// return whether success to quit
     #[allow(clippy::arithmetic_side_effects)]
     fn try_quit(&mut self) -> bool {
+        let dirty = self.document.is_dirty()
+            || self
+                .buffers
+                .iter()
+                .any(|buffer| buffer.document().is_dirty());
+        if self.confirm_discard(dirty, "Ctrl-Q", "quit") {
+            self.should_quit = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// count down the presses of `key` needed to drop unsaved changes,
+    /// return whether `action` may go on
+    #[allow(clippy::arithmetic_side_effects)]
+    fn confirm_discard(&mut self, dirty: bool, key: &'static str, action: &str) -> bool {
+        // presses of another key start counting again
+        if self.discard_key != Some(key) {
+            self.quit_times = QUIT_TIMES;
+            self.discard_key = Some(key);
+        }
         self.quit_times -= 1;
-        if self.quit_times > 0 && self.document.is_dirty() {
-            // let quit_check_msg =
+        if self.quit_times > 0 && dirty {
             self.status_message = StatusMessage::from(format!(
-                "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
-                self.quit_times
+                "WARNING! File has unsaved changes. Press {} {} more times to {}.",
+                key, self.quit_times, action
             ));
             false
         } else {
-            self.should_quit = true;
             true
         }
     }
 
     fn reset_quit(&mut self) {
+        self.discard_key = None;
         if self.quit_times < QUIT_TIMES {
             self.quit_times = QUIT_TIMES;
             self.status_message = StatusMessage::from("");
         }
     }
 
+    /// replace the selected text by `text`, return whether there was a selection
+    fn replace_selection(&mut self, text: &str) -> bool {
+        if let Some((start, end)) = self.selection_range() {
+            self.cursor_position = self.document.replace(&start, &end, text);
+            self.selection_anchor = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn toggle_line_numbers(&mut self) {
+        let line_numbers = self.config.line_numbers().next();
+        self.config.set_line_numbers(line_numbers);
+        self.status_message = StatusMessage::from(format!("Line numbers: {:?}", line_numbers));
+    }
+
+    fn toggle_soft_wrap(&mut self) {
+        let soft_wrap = !self.config.soft_wrap();
+        self.config.set_soft_wrap(soft_wrap);
+        self.offset.x = 0;
+        self.wrap_offset = 0;
+        self.status_message = StatusMessage::from(if soft_wrap {
+            "Soft wrap on"
+        } else {
+            "Soft wrap off"
+        });
+    }
+
+    /// insert a tab, or spaces up to the next indentation stop with `expand_tab`
+    fn insert_tab(&mut self) {
+        let text = if self.config.expand_tab() {
+            let width = self.config.indent_width().max(1);
+            let column = self.cursor_column();
+            " ".repeat(width.saturating_sub(column % width))
+        } else {
+            String::from("\t")
+        };
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+    }
+
+    /// indent or dedent the cursor row or the selected rows by one level
+    fn indent(&mut self, increase: bool) {
+        let (first, last) = match self.selection_range() {
+            // a selection ending at the start of a row does not include that row
+            Some((start, end)) if end.x == 0 && end.y > start.y => {
+                (start.y, end.y.saturating_sub(1))
+            }
+            Some((start, end)) => (start.y, end.y),
+            None => (self.cursor_position.y, self.cursor_position.y),
+        };
+        let cursor_length = self.document.row_length(self.cursor_position.y);
+        let anchor_length = self
+            .selection_anchor
+            .as_ref()
+            .map(|anchor| self.document.row_length(anchor.y));
+        let indent_unit = self.config.indent_unit();
+        if increase {
+            self.document.indent_lines(first, last, &indent_unit);
+        } else {
+            self.document.dedent_lines(first, last, &indent_unit);
+        }
+
+        // keep the cursor and the anchor next to the same text
+        let shift = |position: &mut Position, old_length: usize, document: &Document| {
+            if position.y < first || position.y > last || position.x == 0 {
+                return;
+            }
+            let new_length = document.row_length(position.y);
+            position.x = if new_length >= old_length {
+                position
+                    .x
+                    .saturating_add(new_length.saturating_sub(old_length))
+            } else {
+                position
+                    .x
+                    .saturating_sub(old_length.saturating_sub(new_length))
+            };
+        };
+        shift(&mut self.cursor_position, cursor_length, &self.document);
+        if let (Some(anchor), Some(length)) = (self.selection_anchor.as_mut(), anchor_length) {
+            shift(anchor, length, &self.document);
+        }
+    }
+
+    fn toggle_auto_fill(&mut self) {
+        let auto_fill = !self.config.auto_fill();
+        self.config.set_auto_fill(auto_fill);
+        self.status_message = StatusMessage::from(if auto_fill {
+            format!("Auto fill on at column {}", self.config.fill_column())
+        } else {
+            String::from("Auto fill off")
+        });
+    }
+
+    fn reflow(&mut self) {
+        self.selection_anchor = None;
+        self.cursor_position = self.document.reflow(
+            &self.cursor_position,
+            self.config.fill_column(),
+            self.config.tab_width(),
+        );
+    }
+
+    fn copy(&mut self) {
+        if let Some((start, end)) = self.selection_range() {
+            self.clipboard = self.document.text_range(&start, &end);
+            Terminal::set_clipboard(&self.clipboard);
+            self.status_message = StatusMessage::from("Copied selection");
+        }
+    }
+
+    fn cut(&mut self) {
+        if self.selection_range().is_some() {
+            self.copy();
+            self.replace_selection("");
+            self.status_message = StatusMessage::from("Cut selection");
+        }
+    }
+
+    fn paste(&mut self) {
+        let text = self.clipboard.clone();
+        self.insert_str(&text);
+    }
+
+    /// insert `text` over the selection or at the cursor as a single change
+    fn insert_str(&mut self, text: &str) {
+        if !self.replace_selection(text) {
+            self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+        }
+    }
+
+    fn undo(&mut self) {
+        self.selection_anchor = None;
+        if let Some(position) = self.document.undo() {
+            self.cursor_position = position;
+        } else {
+            self.status_message = StatusMessage::from("Already at oldest change");
+        }
+    }
+
+    fn redo(&mut self) {
+        self.selection_anchor = None;
+        if let Some(position) = self.document.redo() {
+            self.cursor_position = position;
+        } else {
+            self.status_message = StatusMessage::from("Already at newest change");
+        }
+    }
+
     fn search(&mut self) {
+        self.selection_anchor = None;
         let old_position = self.cursor_position.clone();
-        // let mut direction = SearchDirection::Forward;
+        self.search_status.clear();
         let query = self
             .prompt(
-                "Search (ESC to cancel, Arrows to navigate): ",
+                PromptKind::Search,
+                |editor| {
+                    format!(
+                        "Search{}{} (ESC to cancel, Up/Down to navigate, Alt-Up/Down = history, Alt-R/C/W = regex/case/word): ",
+                        editor.search_options, editor.search_status
+                    )
+                },
                 increase_search,
             )
             .unwrap_or_default();
@@ -266,9 +672,125 @@ impl Editor {
         self.highlighted_word = None;
     }
 
+    /// replace the matches after the cursor one by one, asking before each of them
+    fn replace(&mut self) {
+        self.selection_anchor = None;
+        let old_position = self.cursor_position.clone();
+        self.search_status.clear();
+        let pattern = self
+            .prompt(
+                PromptKind::Search,
+                |editor| {
+                    format!(
+                        "Replace{}{} (ESC to cancel, Alt-Up/Down = history, Alt-R/C/W = regex/case/word): ",
+                        editor.search_options, editor.search_status
+                    )
+                },
+                increase_search,
+            )
+            .unwrap_or_default();
+        self.cursor_position = old_position.clone();
+        self.highlighted_word = None;
+        let Some(pattern) = pattern.filter(|pattern| !pattern.is_empty()) else {
+            self.scroll();
+            return;
+        };
+        let query = match Query::new(&pattern, self.search_options) {
+            Ok(query) => query,
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Invalid pattern: {}", err));
+                return;
+            }
+        };
+        let Some(text) = self
+            .prompt(
+                PromptKind::Replacement,
+                |_| format!("Replace {} with: ", pattern),
+                |_, _, _| {},
+            )
+            .unwrap_or_default()
+        else {
+            self.scroll();
+            return;
+        };
+
+        self.highlighted_word = Some(query.clone());
+        let mut count: usize = 0;
+        let mut at = old_position.clone();
+        // where the pass started, set once it wrapped around to the top
+        let mut stop: Option<Position> = None;
+        loop {
+            let found = self
+                .document
+                .find(&query, &at, SearchDirection::Forward)
+                .filter(|found| stop.as_ref().is_none_or(|stop| found.start < *stop));
+            let Some(found) = found else {
+                if stop.is_some() || old_position.at_beginning() {
+                    break;
+                }
+                stop = Some(old_position.clone());
+                at = Position::default();
+                continue;
+            };
+            self.cursor_position = found.start.clone();
+            self.selection_anchor = Some(found.end.clone());
+            self.scroll();
+            self.status_message =
+                StatusMessage::from("Replace this match? (y = yes, n = no, a = all, q = quit)");
+            if self.refresh_screen().is_err() {
+                break;
+            }
+            let Ok(key) = Terminal::read_key() else {
+                break;
+            };
+            match key.code {
+                KeyCode::Char('y') => {
+                    at = self.document.replace(&found.start, &found.end, &text);
+                    count = count.saturating_add(1);
+                    // the rest of the row moved with the end of the match
+                    if let Some(stop) = stop.as_mut().filter(|stop| stop.y == at.y) {
+                        stop.x = stop.x.saturating_add(at.x).saturating_sub(found.end.x);
+                    }
+                }
+                KeyCode::Char('n') => at = found.end.clone(),
+                KeyCode::Char('a') => {
+                    let until = stop.as_ref().unwrap_or(&old_position);
+                    let (replaced, end) =
+                        self.document
+                            .replace_all(&query, &text, &found.start, until);
+                    at = end;
+                    count = count.saturating_add(replaced);
+                    break;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                _ => (),
+            }
+            // an empty match would be found again at the same place
+            if found.start == found.end && matches!(key.code, KeyCode::Char('y' | 'n')) {
+                at = self.document.next_position(&at);
+            }
+        }
+
+        self.selection_anchor = None;
+        self.highlighted_word = None;
+        self.cursor_position = at;
+        self.scroll();
+        self.status_message = StatusMessage::from(match count {
+            1 => String::from("Replaced 1 match"),
+            _ => format!("Replaced {} matches", count),
+        });
+    }
+
     fn save(&mut self) {
         if self.document.filename().is_none() {
-            let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or(None);
+            let new_name = self
+                .prompt(
+                    PromptKind::Filename,
+                    |_| String::from("Save as (Tab to complete): "),
+                    complete_path,
+                )
+                .unwrap_or(None)
+                .filter(|name| !name.is_empty());
             if let Some(new_name) = new_name {
                 self.document.set_filename(Some(new_name));
             } else {
@@ -287,36 +809,428 @@ impl Editor {
         self.status_message = StatusMessage::from(msg);
     }
 
-    fn prompt<T, C>(&mut self, prompt: T, callback: C) -> Result<Option<String>, io::Error>
+    /// override the detected file type of the document
+    fn choose_filetype(&mut self) {
+        let Some(name) = self
+            .prompt(
+                PromptKind::FileType,
+                |_| String::from("File type (Tab to complete): "),
+                complete_filetype,
+            )
+            .unwrap_or(None)
+            .filter(|name| !name.is_empty())
+        else {
+            return;
+        };
+        match FileType::named(&name) {
+            Some(filetype) => {
+                self.status_message =
+                    StatusMessage::from(format!("File type set to {}", filetype.name()));
+                self.document.set_filetype(filetype);
+            }
+            None => {
+                self.status_message = StatusMessage::from(format!("Unknown file type: {}", name));
+            }
+        }
+    }
+
+    /// Read a line in the message bar, `prompt` builds the label shown before the input.
+    /// `callback` gets every key with the line, so it can react to it or complete the line.
+    /// Return `None` if it is cancelled with ESC. The line is kept in the history of `kind`.
+    fn prompt<P, C>(
+        &mut self,
+        kind: PromptKind,
+        prompt: P,
+        callback: C,
+    ) -> Result<Option<String>, io::Error>
     where
-        T: Display,
-        C: Fn(&mut Self, KeyEvent, &str),
+        P: Fn(&Self) -> String,
+        C: Fn(&mut Self, KeyEvent, &mut PromptLine),
     {
-        let mut result = String::new();
-        loop {
-            self.status_message = StatusMessage::from(format!("{}{}", prompt, result));
-            self.refresh_screen()?;
-            let key = Terminal::read_key()?;
+        let mut line = PromptLine::default();
+        let result = loop {
+            let label = prompt(self);
+            self.prompt_cursor = Some(label.width().saturating_add(line.column()));
+            self.status_message =
+                StatusMessage::from(format!("{}{}{}", label, line.text(), line.hint()));
+            if let Err(err) = self.refresh_screen() {
+                break Err(err);
+            }
+            let key = match Terminal::read_event() {
+                Ok(Event::Key(key)) => key,
+                Ok(Event::Paste(text)) => {
+                    line.insert_str(text.lines().next().unwrap_or_default());
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(err) => break Err(err),
+            };
+            line.set_hint(String::new());
+            let history = self.prompt_history.entry(kind).or_default();
             match key.code {
-                KeyCode::Enter => break,
-                KeyCode::Backspace => result.truncate(result.len().saturating_sub(1)),
-                KeyCode::Esc => {
-                    result.truncate(0);
-                    break;
+                KeyCode::Enter => break Ok(Some(line.text().to_string())),
+                KeyCode::Esc => break Ok(None),
+                KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                    let entry = if key.code == KeyCode::Up {
+                        history.older(line.text())
+                    } else {
+                        history.newer()
+                    };
+                    if let Some(entry) = entry {
+                        line.set_text(entry);
+                    }
                 }
-                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-                    result.push(c);
+                _ => {
+                    line.edit(key);
                 }
-                _ => (),
             }
-            callback(self, key, &result);
-        }
+            callback(self, key, &mut line);
+        };
 
+        self.prompt_cursor = None;
         self.status_message = StatusMessage::from("");
-        if result.is_empty() {
-            Ok(None)
+        let history = self.prompt_history.entry(kind).or_default();
+        match &result {
+            Ok(Some(text)) => history.push(text),
+            _ => history.reset(),
+        }
+        result
+    }
+}
+
+impl Editor {
+    //! buffer functions
+
+    fn take_view(&mut self) -> View {
+        View {
+            cursor_position: mem::take(&mut self.cursor_position),
+            selection_anchor: self.selection_anchor.take(),
+            offset: mem::take(&mut self.offset),
+            wrap_offset: mem::take(&mut self.wrap_offset),
+            highlighted_word: self.highlighted_word.take(),
+        }
+    }
+
+    fn set_view(&mut self, view: View) {
+        self.cursor_position = view.cursor_position;
+        self.selection_anchor = view.selection_anchor;
+        self.offset = view.offset;
+        self.wrap_offset = view.wrap_offset;
+        self.highlighted_word = view.highlighted_word;
+    }
+
+    /// move the shown document and its view into its slot of the buffer list
+    fn store_buffer(&mut self) {
+        let view = self.take_view();
+        let buffer = Buffer::new(mem::take(&mut self.document), view);
+        if let Some(slot) = self.buffers.get_mut(self.current_buffer) {
+            *slot = buffer;
+        }
+    }
+
+    /// show the buffer `index` in the focused pane, its slot is left empty
+    fn load_buffer(&mut self, index: usize) {
+        let Some(slot) = self.buffers.get_mut(index) else {
+            return;
+        };
+        let (document, view) = mem::take(slot).into_parts();
+        self.document = document;
+        self.set_view(view);
+        self.current_buffer = index;
+        if let Some(pane) = self.panes.get_mut(self.current_pane) {
+            pane.buffer = index;
+        }
+    }
+
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.store_buffer();
+        self.load_buffer(index);
+        self.status_message = StatusMessage::from(format!(
+            "Buffer {}/{}: {}",
+            index.saturating_add(1),
+            self.buffers.len(),
+            buffer_name(&self.document)
+        ));
+    }
+
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        let index = if forward {
+            self.current_buffer.saturating_add(1) % count
+        } else {
+            self.current_buffer
+                .checked_sub(1)
+                .unwrap_or(count.saturating_sub(1))
+        };
+        self.switch_buffer(index);
+    }
+
+    /// open a file in a new buffer, or show the buffer which already has it
+    fn open(&mut self) {
+        let Some(filename) = self
+            .prompt(
+                PromptKind::Filename,
+                |_| String::from("Open (Tab to complete): "),
+                complete_path,
+            )
+            .unwrap_or(None)
+            .filter(|name| !name.is_empty())
+        else {
+            return;
+        };
+        if self.document.filename() == Some(&filename) {
+            return;
+        }
+        let open = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.document().filename() == Some(&filename));
+        if let Some(index) = open {
+            self.switch_buffer(index);
+            return;
+        }
+
+        let mut document = match Document::open(&filename) {
+            Ok(document) => document,
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("ERR: Could not open file: {}: {}", filename, err));
+                return;
+            }
+        };
+        document.set_read_only(self.read_only);
+        // an untouched empty buffer is replaced instead of kept around
+        let unused = self.document.filename().is_none()
+            && self.document.is_empty()
+            && !self.document.is_dirty();
+        self.store_buffer();
+        let index = if unused {
+            self.current_buffer
+        } else {
+            self.buffers.len()
+        };
+        let buffer = Buffer::new(document, View::default());
+        if unused {
+            if let Some(slot) = self.buffers.get_mut(index) {
+                *slot = buffer;
+            }
+        } else {
+            self.buffers.push(buffer);
+        }
+        self.load_buffer(index);
+        self.status_message = StatusMessage::from(format!("Opened {}", filename));
+    }
+
+    fn list_buffers(&mut self) {
+        let names: Vec<String> = (0..self.buffers.len())
+            .map(|index| {
+                let document = if index == self.current_buffer {
+                    &self.document
+                } else {
+                    self.buffers
+                        .get(index)
+                        .map(Buffer::document)
+                        .unwrap_or(&self.document)
+                };
+                let modified = if document.is_dirty() { "+" } else { "" };
+                let name = format!(
+                    "{}:{}{}",
+                    index.saturating_add(1),
+                    buffer_name(document),
+                    modified
+                );
+                if index == self.current_buffer {
+                    format!("[{}]", name)
+                } else {
+                    name
+                }
+            })
+            .collect();
+        self.status_message = StatusMessage::from(format!("Buffers: {}", names.join(" ")));
+    }
+
+    /// close the shown buffer, return whether it was closed
+    fn try_close(&mut self) -> bool {
+        if !self.confirm_discard(self.document.is_dirty(), "Ctrl-W", "close it") {
+            return false;
+        }
+        let name = buffer_name(&self.document);
+        if self.buffers.len() > 1 {
+            let removed = self.current_buffer;
+            self.buffers.remove(removed);
+            let index = cmp::min(removed, self.buffers.len().saturating_sub(1));
+            // the other panes on the closed buffer show the next one
+            for pane in &mut self.panes {
+                if pane.buffer == removed {
+                    pane.buffer = index;
+                } else if pane.buffer > removed {
+                    pane.buffer = pane.buffer.saturating_sub(1);
+                }
+            }
+            self.load_buffer(index);
+        } else {
+            self.document = Document::default();
+            self.cursor_position = Position::default();
+            self.selection_anchor = None;
+            self.offset = Position::default();
+            self.wrap_offset = 0;
+            self.highlighted_word = None;
+        }
+        self.status_message = StatusMessage::from(format!("Closed {}", name));
+        true
+    }
+}
+
+impl Editor {
+    //! pane functions
+
+    /// move the focus to the pane `index`, which may show another buffer
+    fn focus_pane(&mut self, index: usize) {
+        if index == self.current_pane {
+            return;
+        }
+        let Some(buffer) = self.panes.get(index).map(|pane| pane.buffer) else {
+            return;
+        };
+        let view = self.take_view();
+        if buffer != self.current_buffer {
+            // the buffer keeps the view it was left with for the next pane showing it
+            let document = mem::take(&mut self.document);
+            if let Some(slot) = self.buffers.get_mut(self.current_buffer) {
+                *slot = Buffer::new(document, view.clone());
+            }
+            if let Some(slot) = self.buffers.get_mut(buffer) {
+                self.document = mem::take(slot).into_parts().0;
+            }
+            self.current_buffer = buffer;
+        }
+        if let Some(pane) = self.panes.get_mut(self.current_pane) {
+            pane.view = view;
+        }
+        let view = self
+            .panes
+            .get_mut(index)
+            .map(|pane| mem::take(&mut pane.view))
+            .unwrap_or_default();
+        self.set_view(view);
+        self.current_pane = index;
+
+        // another pane may have shortened the document
+        let y = cmp::min(self.cursor_position.y, self.document.len());
+        let x = cmp::min(self.cursor_position.x, self.document.row_length(y));
+        self.cursor_position = Position::new(x, y);
+        if self
+            .selection_anchor
+            .as_ref()
+            .is_some_and(|anchor| anchor.y > self.document.len())
+        {
+            self.selection_anchor = None;
+        }
+    }
+
+    /// screen area of the pane `index`
+    fn pane_area(&self, index: usize) -> Rect {
+        let screen = self.screen_area();
+        self.layout
+            .areas(screen)
+            .into_iter()
+            .find(|(pane, _)| *pane == index)
+            .map_or(screen, |(_, area)| area)
+    }
+
+    /// divide the focused pane in two, both show its buffer at the same place
+    fn split_pane(&mut self, direction: SplitDirection) {
+        let (first, second) = self.area.split(direction);
+        if cmp::min(first.height(), second.height()) < 2
+            || cmp::min(first.width(), second.width()) < 2
+        {
+            self.status_message = StatusMessage::from("Pane too small to split");
+            return;
+        }
+        let view = View {
+            cursor_position: self.cursor_position.clone(),
+            selection_anchor: None,
+            offset: self.offset.clone(),
+            wrap_offset: self.wrap_offset,
+            highlighted_word: None,
+        };
+        let index = self.panes.len();
+        self.panes.push(Pane {
+            buffer: self.current_buffer,
+            view,
+        });
+        self.layout.split(self.current_pane, index, direction);
+        self.area = first;
+    }
+
+    /// close the focused pane, the pane which gets its area is focused
+    fn close_pane(&mut self) {
+        if self.panes.len() < 2 {
+            self.status_message = StatusMessage::from("Cannot close the last pane");
+            return;
+        }
+        let closed = self.current_pane;
+        let old_area = self.area;
+        self.layout.remove(closed);
+        let target = self
+            .layout
+            .areas(self.screen_area())
+            .into_iter()
+            .find(|(_, area)| area.contains(old_area.x(), old_area.y()))
+            .map_or(0, |(pane, _)| pane);
+        // the layout is already renumbered, the panes are not yet
+        let target = if target >= closed {
+            target.saturating_add(1)
         } else {
-            Ok(Some(result))
+            target
+        };
+        self.focus_pane(target);
+        self.panes.remove(closed);
+        if self.current_pane > closed {
+            self.current_pane = self.current_pane.saturating_sub(1);
+        }
+        self.area = self.pane_area(self.current_pane);
+    }
+
+    /// focus the nearest pane in the direction of the arrow `key`
+    fn focus_direction(&mut self, key: KeyCode) {
+        let current = self.area;
+        let cursor = self.terminal_cursor_position();
+        let target = self
+            .layout
+            .areas(self.screen_area())
+            .into_iter()
+            .filter(|(pane, _)| *pane != self.current_pane)
+            .filter_map(|(pane, area)| {
+                // distance to the pane, then distance of the cursor to it across the move
+                let distance = match key {
+                    KeyCode::Left if area.right() < current.x() => (
+                        current.x().saturating_sub(area.right()),
+                        distance(cursor.y, area.y(), area.bottom()),
+                    ),
+                    KeyCode::Right if area.x() > current.right() => (
+                        area.x().saturating_sub(current.right()),
+                        distance(cursor.y, area.y(), area.bottom()),
+                    ),
+                    KeyCode::Up if area.bottom() <= current.y() => (
+                        current.y().saturating_sub(area.bottom()),
+                        distance(cursor.x, area.x(), area.right()),
+                    ),
+                    KeyCode::Down if area.y() >= current.bottom() => (
+                        area.y().saturating_sub(current.bottom()),
+                        distance(cursor.x, area.x(), area.right()),
+                    ),
+                    _ => return None,
+                };
+                Some((distance, pane, area))
+            })
+            .min_by_key(|(distance, _, _)| *distance);
+        if let Some((_, pane, area)) = target {
+            self.focus_pane(pane);
+            self.area = area;
         }
     }
 }
@@ -332,12 +1246,7 @@ impl Editor {
             Terminal::clear_screen();
             println!("Goodbye.\r");
         } else {
-            self.document.highlight(
-                self.highlighted_word.as_ref(),
-                Some(self.offset.y().saturating_add(self.terminal_height())),
-            );
-            self.draw_rows();
-            self.draw_status_bar();
+            self.draw_panes();
             self.draw_message_bar();
             Terminal::cursor_set_position(&self.terminal_cursor_position());
         }
@@ -346,46 +1255,169 @@ impl Editor {
         Terminal::flush()
     }
 
-    pub fn draw_row(&self, row: &Row) {
-        let start = self.offset.x;
-        let end = self.offset.x.saturating_add(self.terminal_width());
-        let row = row.render(start, end);
-        println!("{}\r", row);
+    /// draw every pane in its area, focusing each one in turn
+    fn draw_panes(&mut self) {
+        let current = self.current_pane;
+        let screen = self.screen_area();
+        for (pane, area) in self.layout.areas(screen) {
+            self.focus_pane(pane);
+            self.area = area;
+            self.scroll();
+            self.document.highlight(
+                self.highlighted_word.as_ref(),
+                Some(self.offset.y().saturating_add(self.view_height())),
+            );
+            self.draw_rows();
+            self.draw_status_bar(pane == current);
+            if area.right() < screen.right() {
+                for y in area.y()..area.bottom() {
+                    Terminal::cursor_set_position(&Position::new(area.right(), y));
+                    print!("│");
+                }
+            }
+        }
+        self.focus_pane(current);
+        self.area = self.pane_area(current);
+    }
+
+    /// print `text`, which takes `width` columns, on the line `line` of the focused pane
+    /// and blank the rest of the line
+    fn draw_line(&self, line: usize, text: &str, width: usize) {
+        Terminal::cursor_set_position(&Position::new(
+            self.area.x(),
+            self.area.y().saturating_add(line),
+        ));
+        print!(
+            "{}{}",
+            text,
+            " ".repeat(self.area.width().saturating_sub(width))
+        );
+    }
+
+    /// draw the screen columns `start..end` of the row `y` on the line `line`,
+    /// `continued` marks a wrapped line
+    pub fn draw_row(
+        &self,
+        line: usize,
+        row: &Row,
+        y: usize,
+        start: usize,
+        end: usize,
+        continued: bool,
+    ) {
+        let selected = self.selection_range().and_then(|(first, last)| {
+            if y < first.y || y > last.y {
+                return None;
+            }
+            let start = if y == first.y { first.x } else { 0 };
+            let end = if y == last.y { last.x } else { usize::MAX };
+            Some(start..end)
+        });
+        let tab_width = self.config.tab_width();
+        let width = cmp::min(row.column(row.len(), tab_width), end).saturating_sub(start);
+        let text = row.render(start, end, selected, tab_width);
+        let gutter = if continued {
+            " ".repeat(self.gutter_width())
+        } else {
+            self.draw_gutter(y)
+        };
+        self.draw_line(
+            line,
+            &format!("{}{}", gutter, text),
+            width.saturating_add(self.gutter_width()),
+        );
+    }
+
+    fn draw_gutter(&self, y: usize) -> String {
+        let width = self.gutter_width();
+        if width == 0 {
+            return String::new();
+        }
+        let number = match self.config.line_numbers() {
+            LineNumbers::Relative if y != self.cursor_position.y => {
+                y.abs_diff(self.cursor_position.y)
+            }
+            _ => y.saturating_add(1),
+        };
+        let gutter = format!("{:>1$} ", number, width.saturating_sub(1));
+        format!("{}", style::style(gutter).with(LINE_NUMBER_COLOR))
     }
 
     fn draw_rows(&self) {
-        let height = self.terminal_height();
+        if self.config.soft_wrap() {
+            self.draw_wrapped_rows();
+            return;
+        }
+        let height = self.view_height();
+        let (start, end) = (
+            self.offset.x,
+            self.offset.x.saturating_add(self.text_width()),
+        );
         for terminal_row in 0..height {
-            Terminal::clear_current_line();
             #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
             if let Some(row) = self.document.row(terminal_row + self.offset.y) {
-                self.draw_row(row);
+                self.draw_row(
+                    terminal_row,
+                    row,
+                    terminal_row + self.offset.y,
+                    start,
+                    end,
+                    false,
+                );
             } else if self.document.is_empty() && terminal_row == height / 3 {
-                self.draw_welcome_message();
+                self.draw_welcome_message(terminal_row);
             } else {
-                println!("~\r");
+                self.draw_line(terminal_row, "~", 1);
             }
         }
     }
 
-    fn draw_welcome_message(&self) {
-        let mut welcome_message = format!("Editor by Wang Zhen -- version {}\r", VERSION);
-        let width = self.terminal_width();
+    fn draw_wrapped_rows(&self) {
+        let height = self.view_height();
+        let mut line = (self.offset.y, self.wrap_offset);
+        for terminal_row in 0..height {
+            let (y, segment) = line;
+            #[allow(clippy::integer_division)]
+            if let Some(row) = self.document.row(y) {
+                let points = self.wrap_points(y);
+                let tab_width = self.config.tab_width();
+                let start = row.column(points.get(segment).copied().unwrap_or_default(), tab_width);
+                // whitespace hanging at a break is cut at the screen edge
+                let end = points
+                    .get(segment.saturating_add(1))
+                    .map_or(usize::MAX, |next| row.column(*next, tab_width))
+                    .min(start.saturating_add(self.text_width()));
+                self.draw_row(terminal_row, row, y, start, end, segment > 0);
+                line = self.next_line(line);
+            } else if self.document.is_empty() && terminal_row == height / 3 {
+                self.draw_welcome_message(terminal_row);
+            } else {
+                self.draw_line(terminal_row, "~", 1);
+            }
+        }
+    }
+
+    fn draw_welcome_message(&self, line: usize) {
+        let mut welcome_message = format!("Editor by Wang Zhen -- version {}", VERSION);
+        let width = self.area.width();
         #[allow(clippy::integer_division)]
         let padding = width.saturating_sub(welcome_message.len()) / 2;
         let spaces = " ".repeat(padding.saturating_sub(1));
         welcome_message = format!("~{}{}", spaces, welcome_message);
         welcome_message.truncate(width);
-        println!("{}\r", welcome_message);
+        self.draw_line(line, &welcome_message, welcome_message.len());
     }
 
-    fn draw_status_bar(&self) {
+    /// draw the status bar of the focused pane, `active` when it has the keyboard
+    fn draw_status_bar(&self, active: bool) {
         // TODO 构建 StatusInfo 结构，存储相关信息，并实现 Display 用于打印
         // TODO 其中 filename 以什么方式存储？用引用吗
-        let width = self.terminal_width();
+        let width = self.area.width();
         // check is modified
         let modified_indicator = if self.document.is_dirty() {
             " (modified)"
+        } else if self.document.is_read_only() {
+            " (read-only)"
         } else {
             ""
         };
@@ -393,9 +1425,17 @@ impl Editor {
         let mut filename = String::from("[No Name]");
         if let Some(name) = self.document.filename() {
             filename = name.clone();
-            filename.truncate(20);
+            truncate_to_width(&mut filename, 20);
         }
         // number of lines
+        if self.buffers.len() > 1 {
+            filename = format!(
+                "[{}/{}] {}",
+                self.current_buffer.saturating_add(1),
+                self.buffers.len(),
+                filename
+            );
+        }
         let mut status_info = format!(
             "{} - {} lines{}",
             filename,
@@ -410,25 +1450,29 @@ impl Editor {
         );
 
         #[allow(clippy::arithmetic_side_effects)]
-        let len = status_info.len() + line_indicator.len();
+        let len = status_info.width() + line_indicator.width();
         status_info.push_str(&" ".repeat(width.saturating_sub(len)));
         status_info = format!("{}{}", status_info, line_indicator);
-        status_info.truncate(width);
-        println!(
-            "{}",
-            style::style(status_info)
-                .with(STATUS_FG_COLOR)
-                .on(STATUS_BG_COLOR)
-        );
+        truncate_to_width(&mut status_info, width);
+        let background = if active {
+            STATUS_BG_COLOR
+        } else {
+            INACTIVE_STATUS_BG_COLOR
+        };
+        let status_bar = style::style(status_info)
+            .with(STATUS_FG_COLOR)
+            .on(background);
+        self.draw_line(self.view_height(), &format!("{}", status_bar), width);
     }
 
     fn draw_message_bar(&self) {
+        Terminal::cursor_set_position(&Position::new(0, self.screen_area().bottom()));
         Terminal::clear_current_line();
         let message = &self.status_message;
         let delta = Instant::now().duration_since(message.time);
         if delta < Duration::from_secs(5) {
             let mut text = message.text.clone();
-            text.truncate(self.terminal_width());
+            truncate_to_width(&mut text, self.terminal_width());
             print!("{}", text);
         }
     }
@@ -437,19 +1481,83 @@ impl Editor {
 impl Editor {
     //! cursor functions
 
+    /// ordered start and end of the selection, `None` if nothing is selected
+    fn selection_range(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor.as_ref()?;
+        match anchor.cmp(&self.cursor_position) {
+            cmp::Ordering::Less => Some((anchor.clone(), self.cursor_position.clone())),
+            cmp::Ordering::Greater => Some((self.cursor_position.clone(), anchor.clone())),
+            cmp::Ordering::Equal => None,
+        }
+    }
+
     fn terminal_cursor_position(&self) -> Position {
+        if let Some(column) = self.prompt_cursor {
+            return Position {
+                x: cmp::min(column, self.terminal_width().saturating_sub(1)),
+                y: self.terminal_height().saturating_add(1),
+            };
+        }
+        if self.config.soft_wrap() {
+            let (cursor_line, start) = self.cursor_line();
+            let mut line = (self.offset.y, self.wrap_offset);
+            let mut y = 0;
+            while line < cursor_line && y < self.view_height() {
+                line = self.next_line(line);
+                y = y.saturating_add(1);
+            }
+            return Position {
+                x: self
+                    .cursor_column()
+                    .saturating_sub(self.column_of(start, self.cursor_position.y))
+                    .min(self.text_width().saturating_sub(1))
+                    .saturating_add(self.gutter_width())
+                    .saturating_add(self.area.x()),
+                y: y.saturating_add(self.area.y()),
+            };
+        }
         Position {
-            x: self.cursor_position.x.saturating_sub(self.offset.x),
-            y: self.cursor_position.y.saturating_sub(self.offset.y),
+            x: self
+                .cursor_column()
+                .saturating_sub(self.offset.x)
+                .saturating_add(self.gutter_width())
+                .saturating_add(self.area.x()),
+            y: self
+                .cursor_position
+                .y
+                .saturating_sub(self.offset.y)
+                .saturating_add(self.area.y()),
         }
     }
 
+    /// screen column of the grapheme `x` of the row `y`
+    fn column_of(&self, x: usize, y: usize) -> usize {
+        self.document
+            .row(y)
+            .map_or(0, |row| row.column(x, self.config.tab_width()))
+    }
+
+    /// index of the grapheme of the row `y` at the screen column `column`
+    fn index_at_column(&self, column: usize, y: usize) -> usize {
+        self.document.row(y).map_or(0, |row| {
+            row.index_at_column(column, self.config.tab_width())
+        })
+    }
+
+    fn cursor_column(&self) -> usize {
+        self.column_of(self.cursor_position.x, self.cursor_position.y)
+    }
+
     fn move_cursor(&mut self, key: KeyCode) {
         let Position { mut x, mut y } = self.cursor_position;
         let height = self.document.len();
         let mut width = self.document.row_length(y);
+        let column = self.cursor_column();
 
         match key {
+            KeyCode::Up | KeyCode::Down if self.config.soft_wrap() => {
+                (x, y) = self.move_screen_line(x, y, key == KeyCode::Up);
+            }
             KeyCode::Up => y = y.saturating_sub(1),
             KeyCode::Down =>
             {
@@ -477,8 +1585,8 @@ impl Editor {
                     x = 0;
                 }
             }
-            KeyCode::PageUp => y = cmp::max(y.saturating_sub(self.terminal_height()), 0),
-            KeyCode::PageDown => y = cmp::min(y.saturating_add(self.terminal_height()), height),
+            KeyCode::PageUp => y = cmp::max(y.saturating_sub(self.view_height()), 0),
+            KeyCode::PageDown => y = cmp::min(y.saturating_add(self.view_height()), height),
             KeyCode::Home => y = 0,
             KeyCode::End => y = height,
             // KeyCode::Enter=>
@@ -487,6 +1595,14 @@ impl Editor {
                 "error argument to move cursor",
             )),
         }
+        // keep the screen column when moving to another row
+        if matches!(
+            key,
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown
+        ) && !self.config.soft_wrap()
+        {
+            x = self.index_at_column(column, y);
+        }
         // check x is valid
         width = self.document.row_length(y);
         x = cmp::min(x, width);
@@ -496,8 +1612,24 @@ impl Editor {
 
     fn scroll(&mut self) {
         // TODO 修改为不移动 cursor，而移动窗口的版本
-        let (width, height) = self.terminal_size();
+        if self.config.soft_wrap() {
+            self.scroll_wrapped();
+            return;
+        }
+        let height = self.view_height();
+        let width = self.text_width();
 
+        let cursor_column = self.cursor_column();
+        // the cursor cell ends after a wide grapheme, or one column later at the end of the row
+        let cursor_end = cmp::max(
+            self.column_of(
+                self.cursor_position.x.saturating_add(1),
+                self.cursor_position.y,
+            ),
+            cursor_column.saturating_add(1),
+        );
+        let tab_width = self.config.tab_width();
+        let row = self.document.row(self.cursor_position.y);
         let cur_pos = &self.cursor_position;
         let offset = &mut self.offset;
         if cur_pos.y < offset.y {
@@ -505,33 +1637,241 @@ impl Editor {
         } else if cur_pos.y >= offset.y.saturating_add(height) {
             offset.y = cur_pos.y.saturating_sub(height).saturating_add(1);
         }
-        if cur_pos.x < offset.x {
-            offset.x = cur_pos.x;
-        } else if cur_pos.x >= offset.x.saturating_add(width) {
-            offset.x = cur_pos.x.saturating_sub(width).saturating_add(1);
+        if cursor_column < offset.x {
+            offset.x = cursor_column;
+        } else if cursor_end > offset.x.saturating_add(width) {
+            // never start the screen in the middle of a wide grapheme
+            let column = cursor_end.saturating_sub(width);
+            offset.x = row.map_or(column, |row| row.boundary_after(column, tab_width));
         }
     }
 }
 
-fn increase_search(editor: &mut Editor, key: KeyEvent, query: &str) {
+impl Editor {
+    //! soft wrap functions
+
+    /// start index of every screen line of the row `y`
+    fn wrap_points(&self, y: usize) -> Vec<usize> {
+        self.document.row(y).map_or_else(
+            || vec![0],
+            |row| row.wrap_points(self.text_width(), self.config.tab_width()),
+        )
+    }
+
+    /// screen line `(row, segment)` of the cursor and the index its segment starts at
+    fn cursor_line(&self) -> ((usize, usize), usize) {
+        let Position { x, y } = self.cursor_position;
+        let points = self.wrap_points(y);
+        let segment = segment_index(&points, x);
+        (
+            (y, segment),
+            points.get(segment).copied().unwrap_or_default(),
+        )
+    }
+
+    fn previous_line(&self, (y, segment): (usize, usize)) -> Option<(usize, usize)> {
+        if segment > 0 {
+            Some((y, segment.saturating_sub(1)))
+        } else if y > 0 {
+            let y = y.saturating_sub(1);
+            Some((y, self.wrap_points(y).len().saturating_sub(1)))
+        } else {
+            None
+        }
+    }
+
+    fn next_line(&self, (y, segment): (usize, usize)) -> (usize, usize) {
+        if segment.saturating_add(1) < self.wrap_points(y).len() {
+            (y, segment.saturating_add(1))
+        } else {
+            (y.saturating_add(1), 0)
+        }
+    }
+
+    /// cursor position one screen line above or below `(x, y)`, keeping the column
+    fn move_screen_line(&self, x: usize, y: usize, up: bool) -> (usize, usize) {
+        let points = self.wrap_points(y);
+        let segment = segment_index(&points, x);
+        let start = points.get(segment).copied().unwrap_or_default();
+        let column = self
+            .column_of(x, y)
+            .saturating_sub(self.column_of(start, y));
+        let target = if up {
+            self.previous_line((y, segment))
+        } else {
+            Some(self.next_line((y, segment))).filter(|(y, _)| *y <= self.document.len())
+        };
+        let Some((y, segment)) = target else {
+            return (x, y);
+        };
+        let points = self.wrap_points(y);
+        let start = points.get(segment).copied().unwrap_or_default();
+        let end = points
+            .get(segment.saturating_add(1))
+            .map_or(self.document.row_length(y), |next| next.saturating_sub(1));
+        let x = self.index_at_column(self.column_of(start, y).saturating_add(column), y);
+        (cmp::min(x, end), y)
+    }
+
+    fn scroll_wrapped(&mut self) {
+        let height = self.view_height();
+        self.offset.x = 0;
+        self.wrap_offset = cmp::min(
+            self.wrap_offset,
+            self.wrap_points(self.offset.y).len().saturating_sub(1),
+        );
+        let (cursor_line, _) = self.cursor_line();
+        if cursor_line < (self.offset.y, self.wrap_offset) {
+            (self.offset.y, self.wrap_offset) = cursor_line;
+            return;
+        }
+        let mut top = cursor_line;
+        for _ in 1..height {
+            match self.previous_line(top) {
+                Some(line) => top = line,
+                None => break,
+            }
+        }
+        if top > (self.offset.y, self.wrap_offset) {
+            (self.offset.y, self.wrap_offset) = top;
+        }
+    }
+}
+
+/// whether `key` changes the document or writes it
+fn is_edit(key: &KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Enter | KeyCode::Tab | KeyCode::BackTab | KeyCode::Delete | KeyCode::Backspace => {
+            true
+        }
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            matches!(c, 'r' | 's' | 'x' | 'v' | 'z' | 'y')
+        }
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => c == 'q',
+        KeyCode::Char(_) => true,
+        _ => false,
+    }
+}
+
+/// cursor position of the `line` and `column` asked for `file`, kept inside `document`
+fn start_position(document: &Document, file: &FileArg) -> Position {
+    let y = cmp::min(
+        file.line().unwrap_or(1).saturating_sub(1),
+        document.len().saturating_sub(1),
+    );
+    let x = cmp::min(
+        file.column().unwrap_or(1).saturating_sub(1),
+        document.row_length(y),
+    );
+    Position::new(x, y)
+}
+
+/// how far `value` is outside of `start..end`
+fn distance(value: usize, start: usize, end: usize) -> usize {
+    if value < start {
+        start.saturating_sub(value)
+    } else {
+        value.saturating_sub(end.saturating_sub(1))
+    }
+}
+
+/// file name of the document of a buffer, for the buffer list
+fn buffer_name(document: &Document) -> String {
+    document
+        .filename()
+        .cloned()
+        .unwrap_or_else(|| String::from("[No Name]"))
+}
+
+/// cut `text` so that it takes at most `width` terminal columns
+fn truncate_to_width(text: &mut String, width: usize) {
+    let mut columns: usize = 0;
+    let end = text
+        .grapheme_indices(true)
+        .find(|(_, grapheme)| {
+            columns = columns.saturating_add(grapheme.width());
+            columns > width
+        })
+        .map_or(text.len(), |(index, _)| index);
+    text.truncate(end);
+}
+
+/// index of the screen line of a wrapped row which shows the grapheme `x`
+fn segment_index(points: &[usize], x: usize) -> usize {
+    points
+        .partition_point(|&point| point <= x)
+        .saturating_sub(1)
+}
+
+/// prompt callback completing file and directory names on Tab
+fn complete_path(_: &mut Editor, key: KeyEvent, line: &mut PromptLine) {
+    if key.code == KeyCode::Tab {
+        line.complete(&prompt::path_completions(line.text()));
+    }
+}
+
+/// prompt callback completing the names of file types on Tab
+fn complete_filetype(_: &mut Editor, key: KeyEvent, line: &mut PromptLine) {
+    if key.code == KeyCode::Tab {
+        let prefix = line.text().to_lowercase();
+        let completions: Vec<Completion> = filetype::names()
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&prefix))
+            .map(|name| Completion::new(name.to_string(), name.to_string()))
+            .collect();
+        line.complete(&completions);
+    }
+}
+
+fn increase_search(editor: &mut Editor, key: KeyEvent, line: &mut PromptLine) {
     let mut moved = false;
     let direction = match key.code {
-        KeyCode::Right | KeyCode::Down => {
+        KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+            SearchDirection::Forward
+        }
+        KeyCode::Down => {
             editor.move_cursor(KeyCode::Right);
             moved = true;
             SearchDirection::Forward
         }
-        KeyCode::Left | KeyCode::Up => SearchDirection::Backward,
+        KeyCode::Up => SearchDirection::Backward,
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+            match c {
+                'r' => editor.search_options.toggle_regex(),
+                'c' => editor.search_options.toggle_case_insensitive(),
+                'w' => editor.search_options.toggle_whole_word(),
+                _ => (),
+            }
+            SearchDirection::Forward
+        }
         _ => SearchDirection::Forward,
     };
-    if let Some(pos) = editor
-        .document
-        .find(query, &editor.cursor_position, direction)
+    editor.search_status.clear();
+    let Ok(query) = Query::new(line.text(), editor.search_options) else {
+        // an incomplete regex, keep the cursor until it compiles
+        editor.highlighted_word = None;
+        editor.search_status = String::from(" invalid pattern");
+        return;
+    };
+    if let Some((found, wrapped)) =
+        editor
+            .document
+            .find_wrapping(&query, &editor.cursor_position, direction)
     {
-        editor.cursor_position = pos;
+        editor.cursor_position = found.start;
         editor.scroll();
-    } else if moved {
-        editor.move_cursor(KeyCode::Left);
+        let (index, total) = editor.document.match_count(&query, &editor.cursor_position);
+        editor.search_status = format!(" {} of {} matches", index, total);
+        if wrapped {
+            editor.search_status.push_str(", search wrapped");
+        }
+    } else {
+        if moved {
+            editor.move_cursor(KeyCode::Left);
+        }
+        if !query.pattern().is_empty() {
+            editor.search_status = String::from(" no matches");
+        }
     }
-    editor.highlighted_word = Some(query.to_string());
+    editor.highlighted_word = Some(query);
 }
@@ -1,48 +1,238 @@
-#[derive(Debug, Default)]
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// Definitions compiled into the editor, a user file with the same name replaces one.
+const DEFAULT_SYNTAXES: &[(&str, &str)] = &[
+    ("c.toml", include_str!("../syntax/c.toml")),
+    ("cpp.toml", include_str!("../syntax/cpp.toml")),
+    ("dockerfile.toml", include_str!("../syntax/dockerfile.toml")),
+    ("go.toml", include_str!("../syntax/go.toml")),
+    ("javascript.toml", include_str!("../syntax/javascript.toml")),
+    ("json.toml", include_str!("../syntax/json.toml")),
+    ("make.toml", include_str!("../syntax/make.toml")),
+    ("markdown.toml", include_str!("../syntax/markdown.toml")),
+    ("python.toml", include_str!("../syntax/python.toml")),
+    ("rust.toml", include_str!("../syntax/rust.toml")),
+    ("shell.toml", include_str!("../syntax/shell.toml")),
+    ("toml.toml", include_str!("../syntax/toml.toml")),
+    ("typescript.toml", include_str!("../syntax/typescript.toml")),
+    ("yaml.toml", include_str!("../syntax/yaml.toml")),
+];
+
+/// Lexers written for a language, used instead of the generic rules of a definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lexer {
+    /// only takes the keywords from the definition
+    Rust,
+}
+
+/// Delimiters of a comment spanning several lines.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockComment {
+    start: String,
+    end: String,
+}
+
+impl BlockComment {
+    #[must_use]
+    #[inline]
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn end(&self) -> &str {
+        &self.end
+    }
+}
+
+/// How numbers are written, numbers are not highlighted without them.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberRules {
+    /// prefixes such as `0x`, the digits after them may be letters
+    prefixes: Vec<String>,
+    /// digit separator such as `_`
+    separator: Option<String>,
+}
+
+impl NumberRules {
+    #[must_use]
+    #[inline]
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn separator(&self) -> Option<&str> {
+        self.separator.as_deref()
+    }
+}
+
+/// A syntax definition as it is written in a TOML file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SyntaxDefinition {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    /// whole file names, such as `Makefile`
+    #[serde(default)]
+    filenames: Vec<String>,
+    /// programs named by a shebang, a version after the name is ignored
+    #[serde(default)]
+    interpreters: Vec<String>,
+    /// other names a modeline may use, the name itself matches in any case
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    braces: bool,
+    lexer: Option<Lexer>,
+    #[serde(default)]
+    line_comments: Vec<String>,
+    block_comment: Option<BlockComment>,
+    #[serde(default)]
+    strings: Vec<String>,
+    #[serde(default)]
+    raw_strings: Vec<String>,
+    #[serde(default)]
+    multiline_strings: Vec<String>,
+    #[serde(default)]
+    characters: Vec<String>,
+    escape: Option<String>,
+    numbers: Option<NumberRules>,
+    #[serde(default)]
+    primary_keywords: Vec<String>,
+    #[serde(default)]
+    secondary_keywords: Vec<String>,
+    #[serde(default)]
+    headings: Vec<String>,
+    #[serde(default)]
+    emphasis: Vec<String>,
+}
+
+/// The highlighting part of a syntax definition, as the highlighter uses it.
+#[derive(Debug, Default, Clone)]
 pub struct HighlightingOptions {
-    numbers: bool,
-    strings: bool,
-    characters: bool,
-    comments: bool,
-    multiline_comments: bool,
-    primary_keywords: Vec<&'static str>,
-    secondary_keywords: Vec<&'static str>,
+    lexer: Option<Lexer>,
+    line_comments: Vec<String>,
+    block_comment: Option<BlockComment>,
+    strings: Vec<String>,
+    raw_strings: Vec<String>,
+    multiline_strings: Vec<String>,
+    characters: Vec<String>,
+    escape: Option<String>,
+    numbers: Option<NumberRules>,
+    primary_keywords: Vec<String>,
+    secondary_keywords: Vec<String>,
+    headings: Vec<String>,
+    emphasis: Vec<String>,
 }
 
 impl HighlightingOptions {
-    pub fn numbers(&self) -> bool {
-        self.numbers
+    #[must_use]
+    #[inline]
+    pub fn lexer(&self) -> Option<Lexer> {
+        self.lexer
+    }
+
+    /// tokens starting a comment up to the end of the line
+    #[must_use]
+    #[inline]
+    pub fn line_comments(&self) -> &[String] {
+        &self.line_comments
     }
 
-    pub fn strings(&self) -> bool {
-        self.strings
+    #[must_use]
+    #[inline]
+    pub fn block_comment(&self) -> Option<&BlockComment> {
+        self.block_comment.as_ref()
     }
 
-    pub fn characters(&self) -> bool {
-        self.characters
+    /// delimiters of strings, a string ends with the delimiter it starts with
+    #[must_use]
+    #[inline]
+    pub fn strings(&self) -> &[String] {
+        &self.strings
     }
 
-    pub fn comments(&self) -> bool {
-        self.comments
+    /// delimiters of strings without escapes
+    #[must_use]
+    #[inline]
+    pub fn raw_strings(&self) -> &[String] {
+        &self.raw_strings
     }
 
-    pub fn multiline_comments(&self) -> bool {
-        self.multiline_comments
+    /// delimiters of strings which may continue on the next lines
+    #[must_use]
+    #[inline]
+    pub fn multiline_strings(&self) -> &[String] {
+        &self.multiline_strings
     }
 
-    pub fn primary_keywords(&self) -> &Vec<&'static str> {
+    /// delimiters of character literals, which hold a single possibly escaped grapheme
+    #[must_use]
+    #[inline]
+    pub fn characters(&self) -> &[String] {
+        &self.characters
+    }
+
+    /// grapheme escaping the one after it in strings and characters
+    #[must_use]
+    #[inline]
+    pub fn escape(&self) -> Option<&str> {
+        self.escape.as_deref()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn numbers(&self) -> Option<&NumberRules> {
+        self.numbers.as_ref()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn primary_keywords(&self) -> &[String] {
         &self.primary_keywords
     }
 
-    pub fn secondary_keywords(&self) -> &Vec<&'static str> {
+    #[must_use]
+    #[inline]
+    pub fn secondary_keywords(&self) -> &[String] {
         &self.secondary_keywords
     }
+
+    /// tokens which make a heading of the line they start
+    #[must_use]
+    #[inline]
+    pub fn headings(&self) -> &[String] {
+        &self.headings
+    }
+
+    /// delimiters of emphasized text, such as `*` in Markdown
+    #[must_use]
+    #[inline]
+    pub fn emphasis(&self) -> &[String] {
+        &self.emphasis
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileType {
     name: String,
     hl_opts: HighlightingOptions,
+    /// whether blocks are delimited by braces, used by auto indent
+    braces: bool,
 }
 
 impl Default for FileType {
@@ -50,6 +240,31 @@ impl Default for FileType {
         Self {
             name: String::from("No filetype"),
             hl_opts: HighlightingOptions::default(),
+            braces: false,
+        }
+    }
+}
+
+impl From<&SyntaxDefinition> for FileType {
+    fn from(value: &SyntaxDefinition) -> Self {
+        Self {
+            name: value.name.clone(),
+            hl_opts: HighlightingOptions {
+                lexer: value.lexer,
+                line_comments: value.line_comments.clone(),
+                block_comment: value.block_comment.clone(),
+                strings: value.strings.clone(),
+                raw_strings: value.raw_strings.clone(),
+                multiline_strings: value.multiline_strings.clone(),
+                characters: value.characters.clone(),
+                escape: value.escape.clone(),
+                numbers: value.numbers.clone(),
+                primary_keywords: value.primary_keywords.clone(),
+                secondary_keywords: value.secondary_keywords.clone(),
+                headings: value.headings.clone(),
+                emphasis: value.emphasis.clone(),
+            },
+            braces: value.braces,
         }
     }
 }
@@ -58,40 +273,42 @@ impl<T> From<T> for FileType
 where
     T: Into<String>,
 {
+    /// the file type of the definition naming the file, or else its extension
     fn from(value: T) -> Self {
         let filename: String = value.into();
-        if filename.ends_with(".rs") {
-            let primary_keywords = vec![
-                "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false",
-                "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
-                "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
-                "true", "type", "unsafe", "use", "where", "while", "dyn", "abstract", "become",
-                "box", "do", "final", "macro", "override", "priv", "typeof", "unsized", "virtual",
-                "yield", "async", "await", "try",
-            ];
-            let secondary_keywords = vec![
-                "bool", "char", "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64",
-                "usize", "f32", "f64",
-            ];
-            Self {
-                name: String::from("Rust"),
-                hl_opts: HighlightingOptions {
-                    numbers: true,
-                    strings: true,
-                    characters: true,
-                    comments: true,
-                    multiline_comments: true,
-                    primary_keywords,
-                    secondary_keywords,
-                },
-            }
-        } else {
-            Self::default()
-        }
+        by_filename(&filename)
+            .map(FileType::from)
+            .unwrap_or_default()
     }
 }
 
 impl FileType {
+    /// Detect the file type from a vim or emacs modeline in `lines`, then from the name
+    /// of the file and last from the shebang of the first line. `lines` are the first
+    /// and last lines of the file.
+    #[must_use]
+    pub fn detect(filename: Option<&str>, lines: &[&str]) -> Self {
+        lines
+            .iter()
+            .find_map(|line| modeline(line))
+            .and_then(|name| by_name(&name))
+            .or_else(|| filename.and_then(by_filename))
+            .or_else(|| {
+                lines
+                    .first()
+                    .and_then(|line| interpreter(line))
+                    .and_then(by_interpreter)
+            })
+            .map(FileType::from)
+            .unwrap_or_default()
+    }
+
+    /// the file type called `name` or one of its aliases
+    #[must_use]
+    pub fn named(name: &str) -> Option<Self> {
+        by_name(name).map(FileType::from)
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -99,4 +316,269 @@ impl FileType {
     pub fn highlightling_options(&self) -> &HighlightingOptions {
         &self.hl_opts
     }
+
+    pub fn uses_braces(&self) -> bool {
+        self.braces
+    }
+}
+
+/// Syntax definitions loaded once, with the errors of the files which were skipped.
+struct Registry {
+    syntaxes: Vec<SyntaxDefinition>,
+    errors: Vec<String>,
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(load_syntaxes)
+}
+
+/// names of every known file type
+#[must_use]
+pub fn names() -> Vec<&'static str> {
+    registry()
+        .syntaxes
+        .iter()
+        .map(|syntax| syntax.name.as_str())
+        .collect()
+}
+
+fn by_name(name: &str) -> Option<&'static SyntaxDefinition> {
+    registry().syntaxes.iter().find(|syntax| {
+        syntax.name.eq_ignore_ascii_case(name)
+            || syntax
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    })
+}
+
+fn by_filename(filename: &str) -> Option<&'static SyntaxDefinition> {
+    let path = Path::new(filename);
+    let name = path.file_name().and_then(|name| name.to_str());
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let syntaxes = &registry().syntaxes;
+    syntaxes
+        .iter()
+        .find(|syntax| name.is_some_and(|name| syntax.filenames.iter().any(|n| n == name)))
+        .or_else(|| {
+            syntaxes.iter().find(|syntax| {
+                extension.is_some_and(|extension| syntax.extensions.iter().any(|e| e == extension))
+            })
+        })
+}
+
+fn by_interpreter(program: &str) -> Option<&'static SyntaxDefinition> {
+    let unversioned = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    registry().syntaxes.iter().find(|syntax| {
+        syntax
+            .interpreters
+            .iter()
+            .any(|interpreter| interpreter == program || interpreter == unversioned)
+    })
+}
+
+/// program run by the shebang `line`, looking through `env`
+fn interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // skip options such as `-S`
+        words.find(|word| !word.starts_with('-'))
+    } else {
+        Some(program)
+    }
+}
+
+/// file type named by a vim modeline such as `vim: set ft=python :` or an emacs one
+/// such as `-*- mode: python -*-`
+fn modeline(line: &str) -> Option<String> {
+    static VIM: OnceLock<Option<Regex>> = OnceLock::new();
+    static EMACS: OnceLock<Option<Regex>> = OnceLock::new();
+    let vim = VIM
+        .get_or_init(|| Regex::new(r"(?:^|\s)(?:vi|vim|ex):.*?\b(?:ft|filetype)=([\w+.-]+)").ok())
+        .as_ref()?;
+    let emacs = EMACS
+        .get_or_init(|| Regex::new(r"-\*-(.*?)-\*-").ok())
+        .as_ref()?;
+    if let Some(captures) = vim.captures(line) {
+        return captures.get(1).map(|name| name.as_str().to_string());
+    }
+    let variables = emacs.captures(line)?.get(1)?.as_str();
+    if !variables.contains(':') {
+        return Some(variables.trim().to_string());
+    }
+    variables.split(';').find_map(|variable| {
+        let (key, value) = variable.split_once(':')?;
+        (key.trim().eq_ignore_ascii_case("mode")).then(|| value.trim().to_string())
+    })
+}
+
+/// errors of the user syntax definitions which could not be loaded
+#[must_use]
+pub fn syntax_errors() -> &'static [String] {
+    &registry().errors
+}
+
+/// `syntax` in the user config directory, `$XDG_CONFIG_HOME/notepad` or
+/// `~/.config/notepad`
+#[must_use]
+pub fn user_syntax_dir() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("notepad").join("syntax"))
+}
+
+/// the embedded definitions, replaced or extended by the `.toml` files of the user
+fn load_syntaxes() -> Registry {
+    let mut files: Vec<(String, String)> = Vec::new();
+    let mut errors = Vec::new();
+    // tests see the embedded definitions only, whatever the user has installed
+    let user_dir = if cfg!(test) { None } else { user_syntax_dir() };
+    if let Some(entries) = user_dir.and_then(|dir| fs::read_dir(dir).ok()) {
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            match fs::read_to_string(&path) {
+                Ok(source) => files.push((name.to_string(), source)),
+                Err(err) => errors.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+    }
+    // the user definitions come first, so they also win the extensions they share
+    for (name, source) in DEFAULT_SYNTAXES {
+        if !files.iter().any(|(file, _)| file == name) {
+            files.push((name.to_string(), source.to_string()));
+        }
+    }
+
+    let mut syntaxes = Vec::new();
+    for (name, source) in files {
+        match toml::from_str(&source) {
+            Ok(syntax) => syntaxes.push(syntax),
+            Err(err) => errors.push(format!("{}: {}", name, err.message())),
+        }
+    }
+    Registry { syntaxes, errors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{by_interpreter, interpreter, modeline, FileType};
+
+    #[test]
+    fn shebang_interpreters() {
+        for (line, program) in [
+            ("#!/bin/bash", Some("bash")),
+            ("#!/bin/sh -e", Some("sh")),
+            ("#! /usr/bin/python3", Some("python3")),
+            ("#!/usr/bin/env python3", Some("python3")),
+            ("#!/usr/bin/env -S python3 -u", Some("python3")),
+            ("#!/usr/bin/env", None),
+            ("# comment", None),
+            ("", None),
+        ] {
+            assert_eq!(interpreter(line), program, "{line}");
+        }
+    }
+
+    #[test]
+    fn interpreters_with_versions() {
+        for (program, name) in [
+            ("python3", Some("Python")),
+            ("python3.11", Some("Python")),
+            ("pypy3", Some("Python")),
+            ("bash", Some("Shell")),
+            ("node", Some("JavaScript")),
+            ("perl5", None),
+        ] {
+            assert_eq!(
+                by_interpreter(program).map(|syntax| syntax.name.as_str()),
+                name,
+                "{program}"
+            );
+        }
+    }
+
+    #[test]
+    fn modelines() {
+        for (line, name) in [
+            ("# vim: set ft=python :", Some("python")),
+            ("// vim: filetype=rust", Some("rust")),
+            ("/* vi: set sw=4 ts=4 ft=c: */", Some("c")),
+            ("# ex: ft=sh", Some("sh")),
+            ("# -*- mode: ruby -*-", Some("ruby")),
+            ("# -*- coding: utf-8; mode: Python -*-", Some("Python")),
+            ("// -*- C++ -*-", Some("C++")),
+            ("# -*- coding: utf-8 -*-", None),
+            ("let vim = ft=python;", None),
+            ("plain text", None),
+        ] {
+            assert_eq!(modeline(line).as_deref(), name, "{line}");
+        }
+    }
+
+    #[test]
+    fn detect_file_types() {
+        for (filename, lines, name) in [
+            (Some("main.rs"), &[][..], "Rust"),
+            (Some("src/lib.hpp"), &[], "C++"),
+            (Some("Makefile"), &[], "Makefile"),
+            (Some("build/Dockerfile"), &[], "Dockerfile"),
+            (Some("Cargo.lock"), &[], "TOML"),
+            (Some("/home/user/.bashrc"), &[], "Shell"),
+            (Some("notes.txt"), &[], "No filetype"),
+            (None, &[], "No filetype"),
+            // the shebang only counts without a known file name
+            (Some("script"), &["#!/usr/bin/env -S python3 -u"], "Python"),
+            (None, &["#!/bin/bash", "echo"], "Shell"),
+            (Some("run.js"), &["#!/bin/sh"], "JavaScript"),
+            (Some("script"), &["echo", "#!/bin/bash"], "No filetype"),
+            // a modeline wins over the name, in the first or the last lines
+            (Some("config.txt"), &["# vim: set ft=yaml :"], "YAML"),
+            (
+                Some("main.c"),
+                &["int x;", "/* -*- mode: c++ -*- */"],
+                "C++",
+            ),
+            (Some("main.rs"), &["#!/bin/sh", "# vim: ft=sh"], "Shell"),
+            // an unknown modeline falls back to the file name
+            (Some("main.rs"), &["// vim: ft=unknown"], "Rust"),
+        ] {
+            assert_eq!(
+                FileType::detect(filename, lines).name(),
+                name,
+                "{filename:?} {lines:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn named_by_alias() {
+        assert_eq!(
+            FileType::named("py").map(|filetype| filetype.name()),
+            Some("Python".to_string())
+        );
+        assert_eq!(
+            FileType::named("rust").map(|filetype| filetype.name()),
+            Some("Rust".to_string())
+        );
+        assert_eq!(
+            FileType::named("golang").map(|filetype| filetype.name()),
+            Some("Go".to_string())
+        );
+        assert!(FileType::named("cobol").is_none());
+    }
 }
@@ -1,9 +1,16 @@
+use std::iter;
+
 use crossterm::style::Color;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::constants::{
-    CHARACTER_COLOR, COMMENT_COLOR, MATCH_COLOR, NONE_COLOR, NUMBER_COLOR, PRIMARY_KEYWORDS_COLOR,
+    ATTRIBUTE_COLOR, CHARACTER_COLOR, COMMENT_COLOR, EMPHASIS_COLOR, ESCAPE_COLOR, HEADING_COLOR,
+    LIFETIME_COLOR, MACRO_COLOR, MATCH_COLOR, NONE_COLOR, NUMBER_COLOR, PRIMARY_KEYWORDS_COLOR,
     SECONDARY_KEYWORDS_COLOR, STRING_COLOR,
 };
+use crate::filetype::{HighlightingOptions, Lexer};
+
+mod rust;
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum HighlightType {
@@ -17,6 +24,13 @@ pub enum HighlightType {
     MultilineComment,
     PrimaryKeywords,
     SecondaryKeywords,
+    Heading,
+    Emphasis,
+    Lifetime,
+    Macro,
+    Attribute,
+    /// escape sequence inside a string or character
+    Escape,
 }
 
 impl From<&HighlightType> for Color {
@@ -30,12 +44,789 @@ impl From<&HighlightType> for Color {
             HighlightType::Comment | HighlightType::MultilineComment => COMMENT_COLOR,
             HighlightType::PrimaryKeywords => PRIMARY_KEYWORDS_COLOR,
             HighlightType::SecondaryKeywords => SECONDARY_KEYWORDS_COLOR,
+            HighlightType::Heading => HEADING_COLOR,
+            HighlightType::Emphasis => EMPHASIS_COLOR,
+            HighlightType::Lifetime => LIFETIME_COLOR,
+            HighlightType::Macro => MACRO_COLOR,
+            HighlightType::Attribute => ATTRIBUTE_COLOR,
+            HighlightType::Escape => ESCAPE_COLOR,
         }
     }
 }
 
+/// Where the lexer is at the end of a line, the next line starts there.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// inside as many nested block comments
+    BlockComment(usize),
+    /// inside a string opened by this delimiter
+    String(String),
+    /// inside a string without escapes opened by this delimiter
+    RawString(String),
+    /// inside an attribute, as many brackets deep
+    Attribute(usize),
+}
+
 impl Default for &HighlightType {
     fn default() -> Self {
         &HighlightType::None
     }
 }
+
+/// Highlight the graphemes of `line`, which starts in the state `start`. The result
+/// holds exactly one type per grapheme, so it is indexed the same way as the row, and
+/// the state the line ends in.
+#[must_use]
+pub fn highlight_line(
+    line: &str,
+    opts: &HighlightingOptions,
+    start: &LineState,
+) -> (Vec<HighlightType>, LineState) {
+    let mut highlighter = Highlighter {
+        opts,
+        graphemes: line.graphemes(true).collect(),
+        highlighting: Vec::new(),
+        index: 0,
+        state: LineState::Normal,
+    };
+    highlighter.run(start);
+    (highlighter.highlighting, highlighter.state)
+}
+
+struct Highlighter<'a> {
+    opts: &'a HighlightingOptions,
+    graphemes: Vec<&'a str>,
+    highlighting: Vec<HighlightType>,
+    /// grapheme the next token starts at
+    index: usize,
+    /// state at the end of the last token
+    state: LineState,
+}
+
+impl Highlighter<'_> {
+    fn run(&mut self, start: &LineState) {
+        if self.opts.lexer() == Some(Lexer::Rust) {
+            self.run_rust(start);
+            return;
+        }
+        match start {
+            LineState::BlockComment(_) => self.close_multiline_comment(0),
+            LineState::String(delimiter) => self.close_string(delimiter, false, 0),
+            LineState::RawString(delimiter) => self.close_string(delimiter, true, 0),
+            LineState::Normal | LineState::Attribute(_) => {
+                self.highlight_heading();
+            }
+        }
+
+        while let Some(grapheme) = self.graphemes.get(self.index).copied() {
+            if self.highlight_multiline_comment()
+                || self.highlight_char()
+                || self.highlight_comment()
+                || self.highlight_keywords(
+                    self.opts.primary_keywords(),
+                    HighlightType::PrimaryKeywords,
+                )
+                || self.highlight_keywords(
+                    self.opts.secondary_keywords(),
+                    HighlightType::SecondaryKeywords,
+                )
+                || self.highlight_string()
+                || self.highlight_emphasis()
+                || self.highlight_number(grapheme)
+            {
+                continue;
+            }
+            self.push(HighlightType::None, 1);
+        }
+    }
+
+    /// a whole line starting with a heading token
+    fn highlight_heading(&mut self) -> bool {
+        if self
+            .opts
+            .headings()
+            .iter()
+            .any(|heading| self.next_is(0, heading))
+        {
+            self.push(HighlightType::Heading, self.graphemes.len());
+            return true;
+        }
+        false
+    }
+
+    /// mark the next `count` graphemes, never past the end of the line
+    fn push(&mut self, hl_type: HighlightType, count: usize) {
+        let count = count.min(self.graphemes.len().saturating_sub(self.index));
+        self.highlighting.extend(iter::repeat_n(hl_type, count));
+        self.index = self.index.saturating_add(count);
+    }
+
+    /// whether the graphemes `offset` after the current one spell `expected`
+    fn next_is(&self, offset: usize, expected: &str) -> bool {
+        self.is_at(self.index.saturating_add(offset), expected)
+    }
+
+    fn is_at(&self, index: usize, expected: &str) -> bool {
+        !expected.is_empty()
+            && expected
+                .graphemes(true)
+                .enumerate()
+                .all(|(i, grapheme)| self.graphemes.get(index.saturating_add(i)) == Some(&grapheme))
+    }
+
+    /// number of graphemes taken by an escape and the grapheme it escapes at `index`
+    fn escape_at(&self, index: usize) -> Option<usize> {
+        self.opts
+            .escape()
+            .filter(|escape| self.is_at(index, escape))
+            .map(|escape| escape.graphemes(true).count().saturating_add(1))
+    }
+
+    fn highlight_char(&mut self) -> bool {
+        let opts = self.opts;
+        let Some(delimiter) = opts
+            .characters()
+            .iter()
+            .find(|delimiter| self.next_is(0, delimiter))
+        else {
+            return false;
+        };
+        let length = delimiter.graphemes(true).count();
+        let content = self
+            .escape_at(self.index.saturating_add(length))
+            .unwrap_or(1);
+        let closing = length.saturating_add(content);
+        if self.next_is(closing, delimiter) {
+            self.push(HighlightType::Character, closing.saturating_add(length));
+            return true;
+        }
+        false
+    }
+
+    fn highlight_comment(&mut self) -> bool {
+        if self
+            .opts
+            .line_comments()
+            .iter()
+            .any(|comment| self.next_is(0, comment))
+        {
+            self.push(HighlightType::Comment, self.graphemes.len());
+            return true;
+        }
+        false
+    }
+
+    fn highlight_multiline_comment(&mut self) -> bool {
+        let Some(comment) = self.opts.block_comment() else {
+            return false;
+        };
+        if self.next_is(0, comment.start()) {
+            self.close_multiline_comment(comment.start().graphemes(true).count());
+            return true;
+        }
+        false
+    }
+
+    /// mark everything up to the end of the block comment as a multiline comment, the
+    /// search starts `skip` graphemes after the current one
+    fn close_multiline_comment(&mut self, skip: usize) {
+        let Some(end) = self.opts.block_comment().map(|comment| comment.end()) else {
+            return;
+        };
+        let from = self.index.saturating_add(skip);
+        let closing = (from..self.graphemes.len()).find(|index| self.is_at(*index, end));
+        match closing {
+            Some(closing) => {
+                self.push(
+                    HighlightType::MultilineComment,
+                    closing
+                        .saturating_add(end.graphemes(true).count())
+                        .saturating_sub(self.index),
+                );
+                self.state = LineState::Normal;
+            }
+            None => {
+                self.push(HighlightType::MultilineComment, self.graphemes.len());
+                self.state = LineState::BlockComment(1);
+            }
+        }
+    }
+
+    fn highlight_string(&mut self) -> bool {
+        let opts = self.opts;
+        let Some((delimiter, raw)) = opts
+            .strings()
+            .iter()
+            .map(|delimiter| (delimiter, false))
+            .chain(opts.raw_strings().iter().map(|delimiter| (delimiter, true)))
+            .find(|(delimiter, _)| self.next_is(0, delimiter))
+        else {
+            return false;
+        };
+        self.close_string(delimiter, raw, delimiter.graphemes(true).count());
+        true
+    }
+
+    /// mark everything up to the closing `delimiter` as a string, the search starts
+    /// `skip` graphemes after the current one. Escapes are skipped unless it is `raw`.
+    fn close_string(&mut self, delimiter: &str, raw: bool, skip: usize) {
+        let length = delimiter.graphemes(true).count();
+        let mut index = self.index.saturating_add(skip);
+        let mut end = None;
+        while index < self.graphemes.len() {
+            if let Some(escape) = self.escape_at(index).filter(|_| !raw) {
+                index = index.saturating_add(escape);
+            } else if self.is_at(index, delimiter) {
+                end = Some(index.saturating_add(length));
+                break;
+            } else {
+                index = index.saturating_add(1);
+            }
+        }
+        match end {
+            Some(end) => {
+                self.push(HighlightType::String, end.saturating_sub(self.index));
+                self.state = LineState::Normal;
+            }
+            None => {
+                self.push(HighlightType::String, self.graphemes.len());
+                let multiline = self
+                    .opts
+                    .multiline_strings()
+                    .iter()
+                    .any(|multiline| multiline == delimiter);
+                self.state = match (multiline, raw) {
+                    (false, _) => LineState::Normal,
+                    (true, false) => LineState::String(delimiter.to_string()),
+                    (true, true) => LineState::RawString(delimiter.to_string()),
+                };
+            }
+        }
+    }
+
+    /// a span between two emphasis delimiters on the same line, which does not start
+    /// with whitespace
+    fn highlight_emphasis(&mut self) -> bool {
+        let opts = self.opts;
+        let Some(delimiter) = opts
+            .emphasis()
+            .iter()
+            .find(|delimiter| self.next_is(0, delimiter))
+        else {
+            return false;
+        };
+        let length = delimiter.graphemes(true).count();
+        let start = self.index.saturating_add(length);
+        if !self.follows_separator()
+            || self
+                .graphemes
+                .get(start)
+                .is_none_or(|grapheme| is_blank(grapheme) || *grapheme == delimiter)
+        {
+            return false;
+        }
+        let closing = (start.saturating_add(1)..self.graphemes.len())
+            .find(|index| self.is_at(*index, delimiter));
+        match closing {
+            Some(closing) => {
+                self.push(
+                    HighlightType::Emphasis,
+                    closing.saturating_add(length).saturating_sub(self.index),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn highlight_number(&mut self, grapheme: &str) -> bool {
+        let Some(rules) = self.opts.numbers() else {
+            return false;
+        };
+        if !is_digit(grapheme) || !self.follows_separator() {
+            return false;
+        }
+        let separator = rules.separator().unwrap_or_default();
+        let is_separator = |grapheme: &&str| !separator.is_empty() && *grapheme == separator;
+        let prefix = rules
+            .prefixes()
+            .iter()
+            .find(|prefix| self.next_is(0, prefix))
+            .map_or(0, |prefix| prefix.graphemes(true).count());
+        let rest = self
+            .graphemes
+            .iter()
+            .skip(self.index.saturating_add(prefix));
+        let length = if prefix > 0 {
+            // digits of other bases may be letters
+            rest.take_while(|grapheme| {
+                grapheme.chars().all(|c| c.is_ascii_alphanumeric()) || is_separator(grapheme)
+            })
+            .count()
+        } else {
+            rest.take_while(|grapheme| {
+                is_digit(grapheme) || **grapheme == "." || is_separator(grapheme)
+            })
+            .count()
+        };
+        self.push(HighlightType::Number, prefix.saturating_add(length));
+        true
+    }
+
+    fn highlight_keywords(&mut self, keywords: &[String], hl_type: HighlightType) -> bool {
+        if !self.follows_separator() {
+            return false;
+        }
+        for word in keywords {
+            let length = word.graphemes(true).count();
+            let end = self.index.saturating_add(length);
+            let matches = self
+                .graphemes
+                .get(self.index..end)
+                .is_some_and(|candidate| candidate.iter().copied().eq(word.graphemes(true)));
+            let at_boundary = self
+                .graphemes
+                .get(end)
+                .is_none_or(|next| is_separator(next));
+            if length > 0 && matches && at_boundary {
+                self.push(hl_type, length);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn follows_separator(&self) -> bool {
+        self.index
+            .checked_sub(1)
+            .and_then(|previous| self.graphemes.get(previous))
+            .is_none_or(|previous| is_separator(previous))
+    }
+}
+
+fn is_separator(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|c| c.is_ascii_punctuation() || c.is_whitespace())
+}
+
+pub(crate) fn is_blank(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+fn is_digit(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use super::{highlight_line, HighlightType, LineState};
+    use crate::{
+        filetype::FileType,
+        row::Row,
+        search::{Query, SearchOptions},
+    };
+
+    /// lines mixing single byte, multi byte, combining and wide graphemes
+    fn mixed_lines() -> Vec<&'static str> {
+        include_str!("../test.txt").lines().collect()
+    }
+
+    fn highlight(line: &str) -> Vec<HighlightType> {
+        let filetype = FileType::from("test.rs");
+        highlight_line(line, filetype.highlightling_options(), &LineState::Normal).0
+    }
+
+    #[test]
+    fn one_type_per_grapheme() {
+        for text in mixed_lines() {
+            for line in [
+                format!("let {text} = \"{text}\"; // {text}"),
+                format!("/* {text} */ {text} 42"),
+                format!("'{text}' \"{text}"),
+                format!("{text}fn {text}"),
+            ] {
+                assert_eq!(
+                    highlight(&line).len(),
+                    line.graphemes(true).count(),
+                    "{line}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keywords_after_non_ascii_text() {
+        for text in mixed_lines() {
+            let line = format!("\"{text}\" fn u8");
+            let highlighting = highlight(&line);
+            assert!(highlighting
+                .get(..5)
+                .is_some_and(|string| string.iter().all(|hl| *hl == HighlightType::String)));
+            assert_eq!(highlighting.get(5), Some(&HighlightType::None));
+            assert_eq!(
+                highlighting.get(6..8),
+                Some(
+                    &[
+                        HighlightType::PrimaryKeywords,
+                        HighlightType::PrimaryKeywords
+                    ][..]
+                )
+            );
+            assert_eq!(
+                highlighting.get(9..),
+                Some(
+                    &[
+                        HighlightType::SecondaryKeywords,
+                        HighlightType::SecondaryKeywords
+                    ][..]
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn keywords_need_separators() {
+        for text in mixed_lines() {
+            let highlighting = highlight(&format!("fn{text} {text}fn"));
+            assert!(highlighting.iter().all(|hl| *hl == HighlightType::None));
+        }
+    }
+
+    #[test]
+    fn comments_cover_graphemes() {
+        let filetype = FileType::from("test.rs");
+        let opts = filetype.highlightling_options();
+        for text in mixed_lines() {
+            let (highlighting, end) =
+                highlight_line(&format!("{text} /* {text}"), opts, &LineState::Normal);
+            assert_eq!(end, LineState::BlockComment(1));
+            assert_eq!(highlighting.get(3), Some(&HighlightType::None));
+            assert!(highlighting
+                .iter()
+                .skip(4)
+                .all(|hl| *hl == HighlightType::MultilineComment));
+
+            let (highlighting, end) =
+                highlight_line(&format!("{text} */ 1"), opts, &LineState::BlockComment(1));
+            assert_eq!(end, LineState::Normal);
+            assert_eq!(highlighting.len(), 8);
+            assert!(highlighting
+                .iter()
+                .take(6)
+                .all(|hl| *hl == HighlightType::MultilineComment));
+            assert_eq!(highlighting.get(7), Some(&HighlightType::Number));
+        }
+    }
+
+    #[test]
+    fn matches_highlight_whole_graphemes() {
+        let filetype = FileType::from("test.rs");
+        for text in mixed_lines() {
+            let word = text.graphemes(true).next().unwrap_or_default();
+            let query = Query::new(word, SearchOptions::default()).ok();
+            let mut row = Row::from(format!("{text} {text}"));
+            row.highlight(
+                filetype.highlightling_options(),
+                query.as_ref(),
+                &LineState::Normal,
+            );
+            let matches: Vec<bool> = row
+                .highlighting()
+                .iter()
+                .map(|hl| *hl == HighlightType::Match)
+                .collect();
+            assert_eq!(matches, [true, true, true, false, true, true, true]);
+        }
+    }
+
+    /// Highlight `lines` in turn as the file `filename` of the type `name`, and compare
+    /// each line with its snapshot, which has one letter per grapheme: `.` none, `k` and
+    /// `t` primary and secondary keywords, `s` string, `c` character, `n` number, `/`
+    /// comment, `*` multiline comment, `h` heading and `e` emphasis.
+    fn assert_snapshot(filename: &str, name: &str, lines: &[(&str, &str)]) {
+        let filetype = FileType::from(filename);
+        assert_eq!(filetype.name(), name);
+        let mut state = LineState::Normal;
+        for (line, expected) in lines {
+            let (highlighting, end) =
+                highlight_line(line, filetype.highlightling_options(), &state);
+            state = end;
+            let snapshot: String = highlighting
+                .iter()
+                .map(|hl| match hl {
+                    HighlightType::None => '.',
+                    HighlightType::Number => 'n',
+                    HighlightType::Match => 'm',
+                    HighlightType::String => 's',
+                    HighlightType::Character => 'c',
+                    HighlightType::Comment => '/',
+                    HighlightType::MultilineComment => '*',
+                    HighlightType::PrimaryKeywords => 'k',
+                    HighlightType::SecondaryKeywords => 't',
+                    HighlightType::Heading => 'h',
+                    HighlightType::Emphasis => 'e',
+                    HighlightType::Lifetime => 'l',
+                    HighlightType::Macro => '!',
+                    HighlightType::Attribute => '#',
+                    HighlightType::Escape => 'x',
+                })
+                .collect();
+            assert_eq!(snapshot, *expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn c_syntax() {
+        assert_snapshot(
+            "main.c",
+            "C",
+            &[
+                ("#include <stdio.h>", "kkkkkkkk.........."),
+                ("int main(void) { // entry", "ttt......tttt....////////"),
+                (
+                    "    char c = '\\n'; /* start",
+                    "....tttt.....cccc..********",
+                ),
+                (
+                    "       end */ return 0x1F + 10;",
+                    "*************.kkkkkk.nnnn...nn.",
+                ),
+                (
+                    "    printf(\"%d\\\"\", 1'000);",
+                    "...........ssssss..nnnnn..",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn cpp_syntax() {
+        assert_snapshot(
+            "main.cpp",
+            "C++",
+            &[
+                (
+                    "template <typename T> class Box {",
+                    "kkkkkkkk..kkkkkkkk....kkkkk......",
+                ),
+                (
+                    "    constexpr auto size = 0b1010; // bits",
+                    "....kkkkkkkkk.kkkk........nnnnnn..///////",
+                ),
+                (
+                    "    std::string s = \"a\\\"b\"; /* c */ bool ok;",
+                    "....................ssssss..*******.tttt....",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn python_syntax() {
+        assert_snapshot(
+            "script.py",
+            "Python",
+            &[
+                (
+                    "def greet(name: str) -> None:  # say hi",
+                    "kkk.............ttt.....kkkk...////////",
+                ),
+                (
+                    "    print('it\\'s', \"x\", 0x_ff, 1_000.5)",
+                    "..........sssssss..sss..nnnnn..nnnnnnn.",
+                ),
+                (
+                    "    \"\"\"doc\"\"\" if True else self",
+                    "....sssssssss.kk.kkkk.kkkk.kkkk",
+                ),
+                ("x = \"\"\"first", "....ssssssss"),
+                ("second\"\"\" + 1", "sssssssss...n"),
+            ],
+        );
+    }
+
+    #[test]
+    fn go_syntax() {
+        assert_snapshot(
+            "main.go",
+            "Go",
+            &[
+                ("package main", "kkkkkkk....."),
+                (
+                    "func main() { var r rune = 'x'; s := `raw\\` }",
+                    "kkkk..........kkk...tttt...ccc.......ssssss..",
+                ),
+                ("/* block", "********"),
+                ("*/ return 0o17 // done", "**.kkkkkk.nnnn.///////"),
+            ],
+        );
+    }
+
+    #[test]
+    fn javascript_syntax() {
+        assert_snapshot(
+            "app.js",
+            "JavaScript",
+            &[
+                (
+                    "const msg = `hi ${name}`; // template",
+                    "kkkkk.......ssssssssssss..///////////",
+                ),
+                (
+                    "let x = 'a' + \"b\" + 0xff; /* c */",
+                    "kkk.....sss...sss...nnnn..*******",
+                ),
+                (
+                    "export default async function f() { return null; }",
+                    "kkkkkk.kkkkkkk.kkkkk.kkkkkkkk.......kkkkkk.kkkk...",
+                ),
+                ("let t = `a ${b}", "kkk.....sssssss"),
+                ("c` + 1", "ss...n"),
+            ],
+        );
+    }
+
+    #[test]
+    fn typescript_syntax() {
+        assert_snapshot(
+            "app.ts",
+            "TypeScript",
+            &[
+                (
+                    "interface Point { x: number; y: string }",
+                    "kkkkkkkkk............tttttt.....tttttt..",
+                ),
+                (
+                    "const p: Point = { x: 1_0, y: `s` };",
+                    "kkkkk.................nnn.....sss...",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn shell_syntax() {
+        assert_snapshot(
+            "build.sh",
+            "Shell",
+            &[
+                (
+                    "if [ -f \"$HOME/.rc\" ]; then # load",
+                    "kk......sssssssssss....kkkk.//////",
+                ),
+                ("    echo 'single' 42", "....tttt.ssssssss.nn"),
+                ("fi", "kk"),
+            ],
+        );
+        assert_snapshot(
+            ".bashrc",
+            "Shell",
+            &[("export PATH=\"$PATH:/bin\"", "tttttt......ssssssssssss")],
+        );
+    }
+
+    #[test]
+    fn toml_syntax() {
+        assert_snapshot(
+            "Cargo.toml",
+            "TOML",
+            &[
+                ("[package]", "hhhhhhhhh"),
+                ("name = \"notepad\" # crate", ".......sssssssss.///////"),
+                ("edition = '2021'", "..........ssssss"),
+                ("debug = true", "........kkkk"),
+                ("x = 0x1f", "....nnnn"),
+            ],
+        );
+    }
+
+    #[test]
+    fn json_syntax() {
+        assert_snapshot(
+            "data.json",
+            "JSON",
+            &[(
+                "{\"a\": [1, 2.5, true, null], \"b\": \"x\\\"y\"}",
+                ".sss...n..nnn..kkkk..kkkk...sss..ssssss.",
+            )],
+        );
+    }
+
+    #[test]
+    fn yaml_syntax() {
+        assert_snapshot(
+            "ci.yml",
+            "YAML",
+            &[
+                ("---", "hhh"),
+                ("name: \"build\" # job", "......sssssss./////"),
+                ("on: 'push'", "kk..ssssss"),
+                ("retries: 3", ".........n"),
+                ("debug: false", ".......kkkkk"),
+            ],
+        );
+    }
+
+    #[test]
+    fn rust_syntax() {
+        assert_snapshot(
+            "main.rs",
+            "Rust",
+            &[
+                ("#![allow(dead_code)]", "####################"),
+                ("#[derive(Debug, Clone)]", "#######################"),
+                ("struct Parser<'a> {", "kkkkkk........ll..."),
+                ("    text: &'static str,", "...........lllllll.ttt."),
+                ("}", "."),
+                (
+                    "let s = \"a \\\"quoted\\\" \\u{1F600}\\x7f\";",
+                    "kkk.....sssxxssssssxxsxxxxxxxxxxxxxs.",
+                ),
+                (
+                    "let raw = r#\"no \\n \"escape",
+                    "kkk.......ssssssssssssssss",
+                ),
+                ("\"# + br\"bytes\" + b\"\\0\";", "ss...sssssssss...ssxxs."),
+                ("let c = ('\\'', b'x', '❤');", "kkk......cxxc..cccc..ccc.."),
+                ("/* outer /* inner */ still", "**************************"),
+                (
+                    "comment */ println!(\"{}\", 0x_ff_u8);",
+                    "**********.!!!!!!!!.ssss..nnnnnnnn..",
+                ),
+                (
+                    "let n = 1_000.5e-3f64 + 0b1010 != 2usize;",
+                    "kkk.....nnnnnnnnnnnnn...nnnnnn....nnnnnn.",
+                ),
+                ("let r#type = \"multi", "kkk..........ssssss"),
+                ("line\";", "sssss."),
+                ("#[cfg(all(", "##########"),
+                ("    feature = \"]\",", "##################"),
+                ("))] fn f() {}", "###.kk......."),
+            ],
+        );
+    }
+
+    #[test]
+    fn markdown_syntax() {
+        assert_snapshot(
+            "README.md",
+            "Markdown",
+            &[
+                ("# Title", "hhhhhhh"),
+                (
+                    "Some *emphasis*, **bold** and `code`.",
+                    ".....eeeeeeeeee..eeeeeeee.....ssssss.",
+                ),
+                ("* item with snake_case_name", "..........................."),
+                ("```rust", "*******"),
+                ("# not a heading", "***************"),
+                ("```", "***"),
+            ],
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Lexer for Rust, which has raw strings, nested comments, lifetimes, macros and
+//! attributes the generic rules cannot describe.
+
+use super::{is_separator, HighlightType, Highlighter, LineState};
+
+impl Highlighter<'_> {
+    pub(super) fn run_rust(&mut self, start: &LineState) {
+        match start {
+            LineState::BlockComment(depth) => self.close_nested_comment(*depth),
+            LineState::String(closing) => self.close_rust_string(closing, false),
+            LineState::RawString(closing) => self.close_rust_string(closing, true),
+            LineState::Attribute(depth) => self.close_attribute(*depth),
+            LineState::Normal => (),
+        }
+
+        while let Some(grapheme) = self.graphemes.get(self.index).copied() {
+            if self.next_is(0, "//") {
+                self.push(HighlightType::Comment, self.graphemes.len());
+            } else if self.next_is(0, "/*") {
+                self.push(HighlightType::MultilineComment, 2);
+                self.close_nested_comment(1);
+            } else if grapheme == "\"" {
+                self.push(HighlightType::String, 1);
+                self.close_rust_string("\"", false);
+            } else if grapheme == "'" {
+                self.highlight_rust_char();
+            } else if self.next_is(0, "#[") || self.next_is(0, "#![") {
+                self.close_attribute(0);
+            } else if is_identifier(grapheme) && self.follows_separator() {
+                self.highlight_word();
+            } else {
+                self.push(HighlightType::None, 1);
+            }
+        }
+    }
+
+    /// mark everything up to the end of `depth` nested block comments
+    fn close_nested_comment(&mut self, mut depth: usize) {
+        let mut index = self.index;
+        while index < self.graphemes.len() {
+            if self.is_at(index, "/*") {
+                depth = depth.saturating_add(1);
+                index = index.saturating_add(2);
+            } else if self.is_at(index, "*/") {
+                depth = depth.saturating_sub(1);
+                index = index.saturating_add(2);
+                if depth == 0 {
+                    break;
+                }
+            } else {
+                index = index.saturating_add(1);
+            }
+        }
+        self.push(
+            HighlightType::MultilineComment,
+            index.saturating_sub(self.index),
+        );
+        self.state = if depth == 0 {
+            LineState::Normal
+        } else {
+            LineState::BlockComment(depth)
+        };
+    }
+
+    /// mark a string up to `closing`, marking its escapes unless it is `raw`. Rust
+    /// strings may span lines, so an unclosed one carries on in the next line.
+    fn close_rust_string(&mut self, closing: &str, raw: bool) {
+        while self.index < self.graphemes.len() {
+            if self.next_is(0, closing) {
+                self.push(HighlightType::String, closing.len());
+                self.state = LineState::Normal;
+                return;
+            }
+            match self.escape_length().filter(|_| !raw) {
+                Some(length) => self.push(HighlightType::Escape, length),
+                None => self.push(HighlightType::String, 1),
+            }
+        }
+        self.state = if raw {
+            LineState::RawString(closing.to_string())
+        } else {
+            LineState::String(closing.to_string())
+        };
+    }
+
+    /// number of graphemes of the escape sequence at the current grapheme, such as
+    /// `\n`, `\x7f` or `\u{1F600}`
+    fn escape_length(&self) -> Option<usize> {
+        if !self.next_is(0, "\\") {
+            return None;
+        }
+        let length = if self.next_is(1, "u") && self.next_is(2, "{") {
+            self.graphemes
+                .iter()
+                .skip(self.index)
+                .position(|grapheme| *grapheme == "}")
+                .map_or(2, |closing| closing.saturating_add(1))
+        } else if self.next_is(1, "x") {
+            4
+        } else {
+            2
+        };
+        Some(length)
+    }
+
+    /// a character such as `'a'` or `'\n'`, else a lifetime such as `'a`
+    fn highlight_rust_char(&mut self) {
+        self.index = self.index.saturating_add(1);
+        let escape = self.escape_length();
+        let content = escape.unwrap_or(1);
+        let is_char = self
+            .graphemes
+            .get(self.index)
+            .is_some_and(|grapheme| *grapheme != "'")
+            && self.next_is(content, "'");
+        self.index = self.index.saturating_sub(1);
+
+        if is_char {
+            self.push(HighlightType::Character, 1);
+            match escape {
+                Some(length) => self.push(HighlightType::Escape, length),
+                None => self.push(HighlightType::Character, 1),
+            }
+            self.push(HighlightType::Character, 1);
+        } else if self
+            .graphemes
+            .get(self.index.saturating_add(1))
+            .is_some_and(|grapheme| is_identifier(grapheme))
+        {
+            let length = self.identifier_length(self.index.saturating_add(1));
+            self.push(HighlightType::Lifetime, length.saturating_add(1));
+        } else {
+            self.push(HighlightType::None, 1);
+        }
+    }
+
+    /// Mark an attribute such as `#[...]` or `#![...]` up to the bracket closing the
+    /// `depth` open ones. Brackets in its strings do not count, and an unclosed attribute
+    /// carries on in the next line.
+    fn close_attribute(&mut self, mut depth: usize) {
+        let mut index = self.index;
+        let mut in_string = false;
+        while let Some(grapheme) = self.graphemes.get(index) {
+            index = index.saturating_add(1);
+            match *grapheme {
+                "\\" if in_string => index = index.saturating_add(1),
+                "\"" => in_string = !in_string,
+                "[" if !in_string => depth = depth.saturating_add(1),
+                "]" if !in_string => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.push(HighlightType::Attribute, index.saturating_sub(self.index));
+        self.state = if depth == 0 {
+            LineState::Normal
+        } else {
+            LineState::Attribute(depth)
+        };
+    }
+
+    /// an identifier, keyword, macro name, number or a string with a prefix
+    fn highlight_word(&mut self) {
+        let length = self.identifier_length(self.index);
+        let end = self.index.saturating_add(length);
+        let word = self
+            .graphemes
+            .get(self.index..end)
+            .unwrap_or_default()
+            .concat();
+        let after = self.graphemes.get(end).copied().unwrap_or_default();
+
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            self.highlight_rust_number();
+        } else if matches!(word.as_str(), "r" | "br" | "cr") && self.raw_string_at(end).is_some() {
+            let hashes = self.raw_string_at(end).unwrap_or_default();
+            self.push(
+                HighlightType::String,
+                length.saturating_add(hashes).saturating_add(1),
+            );
+            self.close_rust_string(&format!("\"{}", "#".repeat(hashes)), true);
+        } else if word == "r" && after == "#" {
+            // a raw identifier such as `r#type` is no keyword
+            let length = self.identifier_length(end.saturating_add(1));
+            self.push(HighlightType::None, length.saturating_add(2));
+        } else if matches!(word.as_str(), "b" | "c") && after == "\"" {
+            self.push(HighlightType::String, 2);
+            self.close_rust_string("\"", false);
+        } else if word == "b" && after == "'" {
+            self.push(HighlightType::Character, 1);
+            self.highlight_rust_char();
+        } else if after == "!" && !self.is_at(end.saturating_add(1), "=") {
+            self.push(HighlightType::Macro, length.saturating_add(1));
+        } else if self.opts.primary_keywords().contains(&word) {
+            self.push(HighlightType::PrimaryKeywords, length);
+        } else if self.opts.secondary_keywords().contains(&word) {
+            self.push(HighlightType::SecondaryKeywords, length);
+        } else {
+            self.push(HighlightType::None, length);
+        }
+    }
+
+    /// number of `#` between a raw string prefix ending before `index` and its quote
+    fn raw_string_at(&self, index: usize) -> Option<usize> {
+        let hashes = self
+            .graphemes
+            .iter()
+            .skip(index)
+            .take_while(|grapheme| **grapheme == "#")
+            .count();
+        self.is_at(index.saturating_add(hashes), "\"")
+            .then_some(hashes)
+    }
+
+    /// a number with an optional `0x`, `0o` or `0b` prefix, `_` separators, a fraction,
+    /// an exponent and a type suffix such as `u8` or `f64`
+    fn highlight_rust_number(&mut self) {
+        let mut index = self.index;
+        let prefixed = ["0x", "0o", "0b"]
+            .iter()
+            .any(|prefix| self.is_at(index, prefix));
+        if prefixed {
+            index = index.saturating_add(2);
+        } else {
+            index = self.skip_digits(index);
+            if self.is_at(index, ".") && self.digit_at(index.saturating_add(1)) {
+                index = self.skip_digits(index.saturating_add(1));
+            }
+            let sign = usize::from(
+                self.is_at(index.saturating_add(1), "+")
+                    || self.is_at(index.saturating_add(1), "-"),
+            );
+            if (self.is_at(index, "e") || self.is_at(index, "E"))
+                && self.digit_at(index.saturating_add(1).saturating_add(sign))
+            {
+                index = self.skip_digits(index.saturating_add(1).saturating_add(sign));
+            }
+        }
+        // hexadecimal digits and the suffix
+        let length = self.identifier_length(index);
+        self.push(
+            HighlightType::Number,
+            index.saturating_add(length).saturating_sub(self.index),
+        );
+    }
+
+    fn digit_at(&self, index: usize) -> bool {
+        self.graphemes
+            .get(index)
+            .is_some_and(|grapheme| grapheme.chars().all(|c| c.is_ascii_digit()))
+    }
+
+    /// index after the digits and `_` separators starting at `index`
+    fn skip_digits(&self, index: usize) -> usize {
+        let digits = self
+            .graphemes
+            .iter()
+            .skip(index)
+            .take_while(|grapheme| {
+                **grapheme == "_" || grapheme.chars().all(|c| c.is_ascii_digit())
+            })
+            .count();
+        index.saturating_add(digits)
+    }
+
+    /// number of identifier graphemes starting at `index`
+    fn identifier_length(&self, index: usize) -> usize {
+        self.graphemes
+            .iter()
+            .skip(index)
+            .take_while(|grapheme| is_identifier(grapheme))
+            .count()
+    }
+}
+
+/// whether `grapheme` may be part of an identifier, which is anything but punctuation
+/// and whitespace except `_`
+fn is_identifier(grapheme: &str) -> bool {
+    grapheme == "_" || !is_separator(grapheme)
+}
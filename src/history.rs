@@ -0,0 +1,257 @@
+use crate::editor::Position;
+
+/// A change of the text. The `x` of its position is a byte offset in the row rather than
+/// a grapheme index, because an edit may merge graphemes, such as a combining mark typed
+/// after a letter, and leave no grapheme index between the old and the new text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Insert { at: Position, text: String },
+    Delete { at: Position, text: String },
+}
+
+impl Edit {
+    #[must_use]
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { at, text } => Edit::Delete {
+                at: at.clone(),
+                text: text.clone(),
+            },
+            Edit::Delete { at, text } => Edit::Insert {
+                at: at.clone(),
+                text: text.clone(),
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn at(&self) -> &Position {
+        match self {
+            Edit::Insert { at, .. } | Edit::Delete { at, .. } => at,
+        }
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        match self {
+            Edit::Insert { text, .. } | Edit::Delete { text, .. } => text,
+        }
+    }
+
+    /// position right after `text` once it is placed at `at`
+    #[must_use]
+    pub fn end(&self) -> Position {
+        text_end(self.at(), self.text())
+    }
+}
+
+/// position of the end of `text` if it starts at `at`, both with byte offsets in the row
+#[must_use]
+pub fn text_end(at: &Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        Some((_, last)) => Position::new(
+            last.len(),
+            at.y().saturating_add(text.matches('\n').count()),
+        ),
+        None => Position::new(at.x().saturating_add(text.len()), at.y()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    id: usize,
+    edits: Vec<Edit>,
+    before: Position,
+    after: Position,
+}
+
+impl Change {
+    #[must_use]
+    #[inline]
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn before(&self) -> &Position {
+        &self.before
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn after(&self) -> &Position {
+        &self.after
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    next_id: usize,
+    /// id of the change on top of `undo_stack` when the document was saved, 0 for none
+    saved: usize,
+    coalesce: bool,
+}
+
+impl History {
+    #[must_use]
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.current() != self.saved
+    }
+
+    #[inline]
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current();
+        self.coalesce = false;
+    }
+
+    fn current(&self) -> usize {
+        self.undo_stack.last().map_or(0, |change| change.id)
+    }
+
+    /// record an edit which has already been applied to the document
+    pub fn record(&mut self, edit: Edit, before: Position, after: Position) {
+        let mergeable = Self::is_mergeable(&edit);
+        self.redo_stack.clear();
+        if mergeable && self.coalesce {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if last.after == before {
+                    if let Some(Edit::Insert { text, .. }) = last.edits.last_mut() {
+                        text.push_str(edit.text());
+                        last.after = after;
+                        return;
+                    }
+                }
+            }
+        }
+        self.push(vec![edit], before, after);
+        self.coalesce = mergeable;
+    }
+
+    /// record several edits as a single undo step
+    pub fn record_group(&mut self, edits: Vec<Edit>, before: Position, after: Position) {
+        if edits.is_empty() {
+            return;
+        }
+        self.redo_stack.clear();
+        self.push(edits, before, after);
+        self.coalesce = false;
+    }
+
+    pub fn undo(&mut self) -> Option<Change> {
+        let change = self.undo_stack.pop()?;
+        self.redo_stack.push(change.clone());
+        self.coalesce = false;
+        Some(change)
+    }
+
+    pub fn redo(&mut self) -> Option<Change> {
+        let change = self.redo_stack.pop()?;
+        self.undo_stack.push(change.clone());
+        self.coalesce = false;
+        Some(change)
+    }
+
+    fn push(&mut self, edits: Vec<Edit>, before: Position, after: Position) {
+        self.next_id = self.next_id.saturating_add(1);
+        self.undo_stack.push(Change {
+            id: self.next_id,
+            edits,
+            before,
+            after,
+        });
+    }
+
+    fn is_mergeable(edit: &Edit) -> bool {
+        match edit {
+            // typing on the row after the last one also inserts the line break before it
+            Edit::Insert { text, .. } => {
+                let typed = text.strip_prefix('\n').unwrap_or(text);
+                typed.chars().count() == 1 && typed != "\n"
+            }
+            Edit::Delete { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_end, Edit, History};
+    use crate::editor::Position;
+
+    fn insert(x: usize, y: usize, text: &str) -> Edit {
+        Edit::Insert {
+            at: Position::new(x, y),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn text_end_counts_bytes_and_lines() {
+        let at = Position::new(3, 2);
+        assert_eq!(text_end(&at, "ab\u{301}"), Position::new(7, 2));
+        assert_eq!(text_end(&at, "a\nb\u{301}"), Position::new(3, 3));
+        assert_eq!(text_end(&at, "a\nbc\nd"), Position::new(1, 4));
+        assert_eq!(text_end(&at, "\n"), Position::new(0, 3));
+    }
+
+    #[test]
+    fn typed_characters_undo_as_one_change() {
+        let mut history = History::default();
+        history.record(insert(0, 0, "a"), Position::new(0, 0), Position::new(1, 0));
+        history.record(insert(1, 0, "b"), Position::new(1, 0), Position::new(2, 0));
+        history.record(insert(2, 0, "\n"), Position::new(2, 0), Position::new(0, 1));
+
+        let change = history.undo().expect("a change to undo");
+        assert_eq!(change.edits(), &[insert(2, 0, "\n")]);
+        let change = history.undo().expect("a change to undo");
+        assert_eq!(change.edits(), &[insert(0, 0, "ab")]);
+        assert_eq!(change.before(), &Position::new(0, 0));
+        assert_eq!(change.after(), &Position::new(2, 0));
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn redo_is_cleared_by_a_new_edit() {
+        let mut history = History::default();
+        history.record(insert(0, 0, "a"), Position::new(0, 0), Position::new(1, 0));
+        assert!(history.undo().is_some());
+        history.record(insert(0, 0, "b"), Position::new(0, 0), Position::new(1, 0));
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn dirty_follows_the_saved_change() {
+        let mut history = History::default();
+        assert!(!history.is_dirty());
+        history.record(insert(0, 0, "a"), Position::new(0, 0), Position::new(1, 0));
+        history.mark_saved();
+        assert!(!history.is_dirty());
+        // typing after a save starts a new change
+        history.record(insert(1, 0, "b"), Position::new(1, 0), Position::new(2, 0));
+        assert!(history.is_dirty());
+        assert!(history.undo().is_some());
+        assert!(!history.is_dirty());
+        assert!(history.undo().is_some());
+        assert!(history.is_dirty());
+        assert!(history.redo().is_some());
+        assert!(!history.is_dirty());
+    }
+
+    #[test]
+    fn inverse_swaps_insert_and_delete() {
+        let edit = insert(1, 2, "x\ny");
+        assert_eq!(
+            edit.inverse(),
+            Edit::Delete {
+                at: Position::new(1, 2),
+                text: "x\ny".to_string(),
+            }
+        );
+        assert_eq!(edit.inverse().inverse(), edit);
+        assert_eq!(edit.end(), Position::new(1, 3));
+    }
+}
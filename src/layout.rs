@@ -0,0 +1,202 @@
+use std::mem;
+
+use crate::buffer::View;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// one pane above the other
+    Horizontal,
+    /// panes side by side, divided by a separator column
+    Vertical,
+}
+
+/// A rectangle of terminal cells.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    #[must_use]
+    #[inline]
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// first column right of the rectangle
+    #[must_use]
+    #[inline]
+    pub fn right(&self) -> usize {
+        self.x.saturating_add(self.width)
+    }
+
+    /// first row below the rectangle
+    #[must_use]
+    #[inline]
+    pub fn bottom(&self) -> usize {
+        self.y.saturating_add(self.height)
+    }
+
+    #[must_use]
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// the two halves of the rectangle, the first one gets the odd cell
+    #[must_use]
+    pub fn split(&self, direction: SplitDirection) -> (Rect, Rect) {
+        match direction {
+            SplitDirection::Horizontal => {
+                let second = self.height / 2;
+                let first = self.height.saturating_sub(second);
+                (
+                    Rect::new(self.x, self.y, self.width, first),
+                    Rect::new(self.x, self.y.saturating_add(first), self.width, second),
+                )
+            }
+            SplitDirection::Vertical => {
+                let width = self.width.saturating_sub(1);
+                let second = width / 2;
+                let first = width.saturating_sub(second);
+                (
+                    Rect::new(self.x, self.y, first, self.height),
+                    Rect::new(
+                        self.x.saturating_add(first).saturating_add(1),
+                        self.y,
+                        second,
+                        self.height,
+                    ),
+                )
+            }
+        }
+    }
+}
+
+/// A part of the screen showing a buffer through its own view.
+#[derive(Debug, Default, Clone)]
+pub struct Pane {
+    pub(crate) buffer: usize,
+    pub(crate) view: View,
+}
+
+/// How the screen is divided into panes, the leaves are indices of panes.
+#[derive(Debug)]
+pub enum Layout {
+    Pane(usize),
+    Split {
+        direction: SplitDirection,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::Pane(0)
+    }
+}
+
+impl Layout {
+    /// divide the area of `pane` between it and `new_pane`, return whether `pane` was found
+    pub fn split(&mut self, pane: usize, new_pane: usize, direction: SplitDirection) -> bool {
+        match self {
+            Layout::Pane(index) if *index == pane => {
+                *self = Layout::Split {
+                    direction,
+                    first: Box::new(Layout::Pane(pane)),
+                    second: Box::new(Layout::Pane(new_pane)),
+                };
+                true
+            }
+            Layout::Pane(_) => false,
+            Layout::Split { first, second, .. } => {
+                first.split(pane, new_pane, direction) || second.split(pane, new_pane, direction)
+            }
+        }
+    }
+
+    /// Give the area of `pane` to its sibling and renumber the panes after it.
+    /// Return whether `pane` was found, the last pane is never removed.
+    pub fn remove(&mut self, pane: usize) -> bool {
+        if !self.remove_leaf(pane) {
+            return false;
+        }
+        self.renumber(pane);
+        true
+    }
+
+    fn remove_leaf(&mut self, pane: usize) -> bool {
+        let Layout::Split { first, second, .. } = self else {
+            return false;
+        };
+        let sibling = match (first.as_mut(), second.as_mut()) {
+            (Layout::Pane(index), sibling) | (sibling, Layout::Pane(index)) if *index == pane => {
+                mem::take(sibling)
+            }
+            _ => return first.remove_leaf(pane) || second.remove_leaf(pane),
+        };
+        *self = sibling;
+        true
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Pane(index) if *index > removed => *index = index.saturating_sub(1),
+            Layout::Pane(_) => (),
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    /// area of every pane when the layout fills `area`
+    #[must_use]
+    pub fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        match self {
+            Layout::Pane(pane) => vec![(*pane, area)],
+            Layout::Split {
+                direction,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = area.split(*direction);
+                let mut areas = first.areas(first_area);
+                areas.extend(second.areas(second_area));
+                areas
+            }
+        }
+    }
+}
@@ -1,7 +1,15 @@
+pub mod buffer;
+pub mod cli;
+pub mod config;
 pub mod constants;
 pub mod document;
 pub mod editor;
 pub mod filetype;
 pub mod highlighting;
+pub mod history;
+pub mod layout;
+pub mod prompt;
+pub mod rope;
 pub mod row;
+pub mod search;
 pub mod terminal;
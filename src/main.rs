@@ -1,8 +1,27 @@
-use std::io;
+use std::{env, io, process};
 
-use notepad::editor;
+use notepad::{
+    cli::{self, Command},
+    constants::VERSION,
+    editor,
+};
 
 fn main() -> Result<(), io::Error> {
-    let mut editor = editor::Editor::default();
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Edit(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::usage());
+            return Ok(());
+        }
+        Ok(Command::Version) => {
+            println!("notepad {}", VERSION);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("notepad: {}\n\n{}", err, cli::usage());
+            process::exit(2);
+        }
+    };
+    let mut editor = editor::Editor::with_options(&options);
     editor.run()
 }
@@ -0,0 +1,284 @@
+use std::fs;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::highlighting::is_blank;
+
+/// Prompts which remember their input separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    Search,
+    Replacement,
+    Filename,
+    FileType,
+}
+
+/// The single line edited in a prompt, `cursor` is a grapheme index.
+#[derive(Debug, Default, Clone)]
+pub struct PromptLine {
+    text: String,
+    cursor: usize,
+    /// shown after the line until the next key, such as the remaining completions
+    hint: String,
+}
+
+/// A text the line of a prompt can be completed to, `name` is what lists it.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    text: String,
+    name: String,
+}
+
+impl Completion {
+    #[must_use]
+    #[inline]
+    pub fn new(text: String, name: String) -> Self {
+        Self { text, name }
+    }
+}
+
+impl PromptLine {
+    #[must_use]
+    #[inline]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn hint(&self) -> &str {
+        &self.hint
+    }
+
+    #[inline]
+    pub fn set_hint(&mut self, hint: String) {
+        self.hint = hint;
+    }
+
+    /// Complete the line to the longest common start of `completions`. When more than
+    /// one of them remains, their names are listed in the hint.
+    pub fn complete(&mut self, completions: &[Completion]) {
+        let Some(first) = completions.first() else {
+            self.hint = String::from(" [no match]");
+            return;
+        };
+        let common = completions
+            .iter()
+            .skip(1)
+            .fold(first.text.as_str(), |common, completion| {
+                common_start(common, &completion.text)
+            });
+        if common.len() > self.text.len() || completions.len() == 1 {
+            self.set_text(common);
+        }
+        if completions.len() > 1 {
+            let names: Vec<&str> = completions
+                .iter()
+                .map(|completion| completion.name.as_str())
+                .collect();
+            self.hint = format!(" [{}]", names.join(" "));
+        }
+    }
+
+    /// replace the whole line and put the cursor at its end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.len();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// terminal columns taken by the text before the cursor
+    #[must_use]
+    pub fn column(&self) -> usize {
+        self.text
+            .get(..self.byte_index(self.cursor))
+            .unwrap_or_default()
+            .width()
+    }
+
+    /// insert `text` at the cursor and move the cursor after it
+    pub fn insert_str(&mut self, text: &str) {
+        let start = self.byte_index(self.cursor);
+        self.text.insert_str(start, text);
+        // the inserted text may combine with the grapheme before it
+        let end = start.saturating_add(text.len());
+        self.cursor = self
+            .text
+            .get(..end)
+            .unwrap_or_default()
+            .graphemes(true)
+            .count();
+    }
+
+    /// remove the graphemes in `start..end`
+    pub fn delete_range(&mut self, start: usize, end: usize) {
+        let (start, end) = (self.byte_index(start), self.byte_index(end));
+        if start < end {
+            self.text.replace_range(start..end, "");
+        }
+    }
+
+    /// Apply an editing key, return whether it was one.
+    pub fn edit(&mut self, key: KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = self.cursor.saturating_add(1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.delete_range(self.cursor, self.cursor.saturating_add(1));
+            }
+            KeyCode::Delete => self.delete_range(self.cursor, self.cursor.saturating_add(1)),
+            KeyCode::Char('w') if control => self.delete_word(),
+            KeyCode::Char('u') if control => self.set_text(""),
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.insert_str(c.encode_utf8(&mut [0; 4]));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// delete the word before the cursor, with the whitespace between them
+    fn delete_word(&mut self) {
+        let graphemes: Vec<&str> = self.text.graphemes(true).take(self.cursor).collect();
+        let blanks = graphemes
+            .iter()
+            .rev()
+            .take_while(|grapheme| is_blank(grapheme))
+            .count();
+        let word = graphemes
+            .iter()
+            .rev()
+            .skip(blanks)
+            .take_while(|grapheme| !is_blank(grapheme))
+            .count();
+        let start = self.cursor.saturating_sub(blanks.saturating_add(word));
+        self.delete_range(start, self.cursor);
+        self.cursor = start;
+    }
+
+    /// byte offset of the grapheme `index`, the text length if it is past the end
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .grapheme_indices(true)
+            .nth(index)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+}
+
+/// Earlier inputs of a prompt, browsed from the newest one back.
+#[derive(Debug, Default)]
+pub struct PromptHistory {
+    entries: Vec<String>,
+    /// entry shown in the prompt, `None` while editing a new line
+    index: Option<usize>,
+    /// the new line, kept while browsing
+    draft: String,
+}
+
+impl PromptHistory {
+    /// add `entry` as the newest entry and stop browsing
+    pub fn push(&mut self, entry: &str) {
+        if !entry.is_empty() {
+            self.entries.retain(|old| old != entry);
+            self.entries.push(entry.to_string());
+        }
+        self.reset();
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.draft.clear();
+    }
+
+    /// the entry before the current one, `current` is kept when browsing starts
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        let index = match self.index {
+            Some(index) => index.checked_sub(1)?,
+            None => {
+                let index = self.entries.len().checked_sub(1)?;
+                self.draft = current.to_string();
+                index
+            }
+        };
+        self.index = Some(index);
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// the entry after the current one, the kept line after the newest entry
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.index?.saturating_add(1);
+        if index < self.entries.len() {
+            self.index = Some(index);
+            self.entries.get(index).map(String::as_str)
+        } else {
+            self.index = None;
+            Some(&self.draft)
+        }
+    }
+}
+
+/// Files and directories whose path starts with `text`, relative to the current
+/// directory. Directories end with a slash, hidden entries are only offered when
+/// `text` names them.
+#[must_use]
+pub fn path_completions(text: &str) -> Vec<Completion> {
+    let (directory, prefix) = match text.rfind('/') {
+        Some(index) => text.split_at(index.saturating_add(1)),
+        None => ("", text),
+    };
+    let Ok(entries) = fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+    let mut completions: Vec<Completion> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let mut name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                name.push('/');
+            }
+            Some(Completion::new(format!("{}{}", directory, name), name))
+        })
+        .collect();
+    completions.sort_by(|a, b| a.name.cmp(&b.name));
+    completions
+}
+
+/// longest start `a` and `b` have in common, cut at a char boundary
+fn common_start<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or_else(|| a.len().min(b.len()), |((index, _), _)| index);
+    a.get(..end).unwrap_or_default()
+}
@@ -0,0 +1,354 @@
+use std::ops::{Index, IndexMut};
+
+use crate::row::Row;
+
+/// number of rows a chunk holds after it is split
+const CHUNK_SIZE: usize = 512;
+
+/// Rows of a document stored as a two level rope: a list of chunks of at most
+/// `2 * CHUNK_SIZE` rows. The chunk lengths are kept in a Fenwick tree, so finding a
+/// row takes `O(log n)` and inserting or removing a row only moves the rows of one
+/// chunk. Adding or dropping a whole chunk rebuilds the tree, which happens at most
+/// once every `CHUNK_SIZE` inserted rows or once per removed range.
+#[derive(Debug, Default)]
+pub struct LineRope {
+    chunks: Vec<Vec<Row>>,
+    lengths: Fenwick,
+    len: usize,
+}
+
+impl LineRope {
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&Row> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks.get(chunk)?.get(offset)
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Row> {
+        let (chunk, offset) = self.locate(index)?;
+        self.chunks.get_mut(chunk)?.get_mut(offset)
+    }
+
+    pub fn push(&mut self, row: Row) {
+        let last = self.chunks.len().saturating_sub(1);
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.len() < CHUNK_SIZE => {
+                chunk.push(row);
+                self.lengths.add(last, 1);
+            }
+            _ => {
+                self.chunks.push(vec![row]);
+                self.lengths.push(1);
+            }
+        }
+        self.len = self.len.saturating_add(1);
+    }
+
+    pub fn insert(&mut self, index: usize, row: Row) {
+        let Some((chunk_index, offset)) = self.locate(index) else {
+            self.push(row);
+            return;
+        };
+
+        let Some(chunk) = self.chunks.get_mut(chunk_index) else {
+            return;
+        };
+        chunk.insert(offset, row);
+        if chunk.len() > CHUNK_SIZE.saturating_mul(2) {
+            let tail = chunk.split_off(CHUNK_SIZE);
+            self.chunks.insert(chunk_index.saturating_add(1), tail);
+            self.rebuild_lengths();
+        } else {
+            self.lengths.add(chunk_index, 1);
+        }
+        self.len = self.len.saturating_add(1);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Row> {
+        let (chunk_index, offset) = self.locate(index)?;
+        let chunk = self.chunks.get_mut(chunk_index)?;
+        let row = chunk.remove(offset);
+        if chunk.is_empty() {
+            self.chunks.remove(chunk_index);
+            self.rebuild_lengths();
+        } else {
+            self.lengths.sub(chunk_index, 1);
+        }
+        self.len = self.len.saturating_sub(1);
+        Some(row)
+    }
+
+    /// remove the rows in `start..end` and return them, draining every chunk once
+    pub fn remove_range(&mut self, start: usize, end: usize) -> Vec<Row> {
+        let end = end.min(self.len);
+        let Some((first, mut offset)) = self.locate(start).filter(|_| start < end) else {
+            return Vec::new();
+        };
+
+        let mut removed = Vec::with_capacity(end.saturating_sub(start));
+        let mut emptied = false;
+        for (index, chunk) in self.chunks.iter_mut().enumerate().skip(first) {
+            let remaining = end.saturating_sub(start).saturating_sub(removed.len());
+            if remaining == 0 {
+                break;
+            }
+            let count = remaining.min(chunk.len().saturating_sub(offset));
+            removed.extend(chunk.drain(offset..offset.saturating_add(count)));
+            self.lengths.sub(index, count);
+            emptied |= chunk.is_empty();
+            offset = 0;
+        }
+        if emptied {
+            self.chunks.retain(|chunk| !chunk.is_empty());
+            self.rebuild_lengths();
+        }
+        self.len = self.len.saturating_sub(removed.len());
+        removed
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Row> {
+        self.chunks.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Row> {
+        self.chunks.iter_mut().flatten()
+    }
+
+    /// chunk holding the row `index` and the offset of the row in it
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        Some(self.lengths.find(index))
+    }
+
+    fn rebuild_lengths(&mut self) {
+        self.lengths = Fenwick::new(self.chunks.iter().map(Vec::len));
+    }
+}
+
+/// A Fenwick tree over the chunk lengths: every node holds the sum of a range of
+/// chunks ending at it, so sums up to a chunk and the chunk holding a row are found by
+/// walking `O(log n)` nodes.
+#[derive(Debug, Default)]
+struct Fenwick {
+    tree: Vec<usize>,
+}
+
+impl Fenwick {
+    fn new(lengths: impl Iterator<Item = usize>) -> Self {
+        let mut tree: Vec<usize> = lengths.collect();
+        for index in 0..tree.len() {
+            let parent = index | index.saturating_add(1);
+            let length = tree.get(index).copied().unwrap_or_default();
+            if let Some(node) = tree.get_mut(parent) {
+                *node = node.saturating_add(length);
+            }
+        }
+        Self { tree }
+    }
+
+    /// add a chunk of `length` rows after the last one
+    fn push(&mut self, length: usize) {
+        let count = self.tree.len();
+        let node = count.saturating_add(1);
+        // the new node covers the chunks after the last node which is not its child
+        let first = node.saturating_sub(node & node.wrapping_neg());
+        let children = self.prefix(count).saturating_sub(self.prefix(first));
+        self.tree.push(length.saturating_add(children));
+    }
+
+    fn add(&mut self, mut index: usize, count: usize) {
+        while let Some(node) = self.tree.get_mut(index) {
+            *node = node.saturating_add(count);
+            index |= index.saturating_add(1);
+        }
+    }
+
+    fn sub(&mut self, mut index: usize, count: usize) {
+        while let Some(node) = self.tree.get_mut(index) {
+            *node = node.saturating_sub(count);
+            index |= index.saturating_add(1);
+        }
+    }
+
+    /// number of rows in the first `count` chunks
+    fn prefix(&self, mut count: usize) -> usize {
+        let mut sum: usize = 0;
+        while count > 0 {
+            sum = sum.saturating_add(
+                self.tree
+                    .get(count.saturating_sub(1))
+                    .copied()
+                    .unwrap_or_default(),
+            );
+            count &= count.saturating_sub(1);
+        }
+        sum
+    }
+
+    /// chunk holding the row `index` and the offset of the row in it, chunks must not be
+    /// empty
+    fn find(&self, index: usize) -> (usize, usize) {
+        let mut chunk: usize = 0;
+        let mut offset = index;
+        let mut step = self.tree.len().checked_ilog2().map_or(0, |bits| 1 << bits);
+        while step > 0 {
+            let next = chunk.saturating_add(step);
+            if let Some(length) = self.tree.get(next.saturating_sub(1)) {
+                if *length <= offset {
+                    chunk = next;
+                    offset = offset.saturating_sub(*length);
+                }
+            }
+            step >>= 1;
+        }
+        (chunk, offset)
+    }
+}
+
+impl FromIterator<Row> for LineRope {
+    fn from_iter<I: IntoIterator<Item = Row>>(iter: I) -> Self {
+        let mut rope = LineRope::default();
+        for row in iter {
+            rope.push(row);
+        }
+        rope
+    }
+}
+
+impl Index<usize> for LineRope {
+    type Output = Row;
+
+    #[allow(clippy::expect_used)]
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("row index out of range")
+    }
+}
+
+impl IndexMut<usize> for LineRope {
+    #[allow(clippy::expect_used)]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.get_mut(index).expect("row index out of range")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineRope, CHUNK_SIZE};
+    use crate::row::Row;
+
+    fn rope(len: usize) -> LineRope {
+        (0..len)
+            .map(|index| Row::from(index.to_string().as_str()))
+            .collect()
+    }
+
+    /// check every row against `expected` through `get` and `iter`
+    fn assert_rows(rope: &LineRope, expected: &[String]) {
+        assert_eq!(rope.len(), expected.len());
+        for (index, row) in expected.iter().enumerate() {
+            assert_eq!(
+                rope.get(index).map(Row::content),
+                Some(row.as_str()),
+                "{index}"
+            );
+        }
+        assert!(rope.get(expected.len()).is_none());
+        assert!(rope.iter().map(Row::content).eq(expected.iter()));
+    }
+
+    fn model(len: usize) -> Vec<String> {
+        (0..len).map(|index| index.to_string()).collect()
+    }
+
+    #[test]
+    fn get_at_chunk_edges() {
+        let len = CHUNK_SIZE * 3 + 1;
+        let rope = rope(len);
+        assert_eq!(rope.chunks.len(), 4);
+        for index in [0, CHUNK_SIZE - 1, CHUNK_SIZE, 2 * CHUNK_SIZE, len - 1] {
+            assert_eq!(
+                rope.get(index).map(Row::content),
+                Some(index.to_string().as_str())
+            );
+        }
+        assert_eq!(rope.locate(CHUNK_SIZE), Some((1, 0)));
+        assert_eq!(rope.locate(CHUNK_SIZE - 1), Some((0, CHUNK_SIZE - 1)));
+        assert_eq!(rope.locate(len - 1), Some((3, 0)));
+        assert_eq!(rope.locate(len), None);
+    }
+
+    #[test]
+    fn insert_splits_a_full_chunk() {
+        let mut rope = rope(CHUNK_SIZE * 2);
+        let mut expected = model(CHUNK_SIZE * 2);
+        // fill the first chunk to twice its size, the next insert splits it
+        for index in 0..=CHUNK_SIZE {
+            rope.insert(index, Row::from("new"));
+            expected.insert(index, "new".to_string());
+        }
+        assert_eq!(rope.chunks.len(), 3);
+        assert_rows(&rope, &expected);
+
+        rope.insert(CHUNK_SIZE, Row::from("edge"));
+        expected.insert(CHUNK_SIZE, "edge".to_string());
+        rope.insert(rope.len(), Row::from("end"));
+        expected.push("end".to_string());
+        assert_rows(&rope, &expected);
+    }
+
+    #[test]
+    fn remove_drops_an_emptied_chunk() {
+        let mut rope = rope(CHUNK_SIZE + 1);
+        let mut expected = model(CHUNK_SIZE + 1);
+        assert_eq!(
+            rope.remove(CHUNK_SIZE).map(|row| row.content().to_string()),
+            expected.pop()
+        );
+        assert_eq!(rope.chunks.len(), 1);
+        assert_rows(&rope, &expected);
+        assert!(rope.remove(CHUNK_SIZE).is_none());
+
+        rope.push(Row::from("pushed"));
+        expected.push("pushed".to_string());
+        assert_rows(&rope, &expected);
+    }
+
+    #[test]
+    fn remove_range_across_chunks() {
+        let len = CHUNK_SIZE * 4;
+        let mut rope = rope(len);
+        let mut expected = model(len);
+        // starts inside the first chunk, empties the second and third, ends in the fourth
+        let (start, end) = (CHUNK_SIZE / 2, CHUNK_SIZE * 3 + 10);
+        let removed: Vec<String> = rope
+            .remove_range(start, end)
+            .iter()
+            .map(|row| row.content().to_string())
+            .collect();
+        assert_eq!(removed, expected.drain(start..end).collect::<Vec<_>>());
+        assert_eq!(rope.chunks.len(), 2);
+        assert_rows(&rope, &expected);
+
+        assert!(rope.remove_range(5, 5).is_empty());
+        assert_eq!(rope.remove_range(rope.len() - 3, rope.len() + 10).len(), 3);
+        expected.truncate(expected.len() - 3);
+        assert_rows(&rope, &expected);
+        assert_eq!(rope.remove_range(0, rope.len()).len(), expected.len());
+        assert_rows(&rope, &[]);
+    }
+}
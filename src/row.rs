@@ -1,21 +1,30 @@
-use std::{cmp, iter};
+use std::{cmp, ops::Range};
 
 use crossterm::style::{self, Stylize};
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
     editor::SearchDirection,
     filetype::HighlightingOptions,
-    highlighting::{self, HighlightType},
+    highlighting::{self, HighlightType, LineState},
+    search::Query,
 };
 
 #[derive(Debug, Default, Clone)]
 pub struct Row {
     content: String,
+    /// byte offset of the start of every grapheme in `content`
+    graphemes: Vec<usize>,
     highlighting: Vec<highlighting::HighlightType>,
-    len: usize,
     modified: bool,
+    /// whether `highlighting` is up to date with the content
     highlighted: bool,
+    /// whether `highlighting` marks search matches
+    matched: bool,
+    /// lexer states the row was last highlighted from and to
+    start_state: LineState,
+    end_state: LineState,
 }
 
 impl<T> From<T> for Row
@@ -24,13 +33,16 @@ where
 {
     fn from(value: T) -> Self {
         let content: String = value.into();
-        let len = content.graphemes(true).count(); // need to be change when editing
+        let graphemes = content.grapheme_indices(true).map(|(i, _)| i).collect();
         Self {
             content,
+            graphemes,
             highlighting: Vec::new(),
-            len,
             modified: false,
             highlighted: false,
+            matched: false,
+            start_state: LineState::default(),
+            end_state: LineState::default(),
         }
     }
 }
@@ -39,7 +51,7 @@ impl Row {
     #[must_use]
     #[inline]
     pub fn len(&self) -> usize {
-        self.len
+        self.graphemes.len()
     }
 
     #[must_use]
@@ -54,9 +66,11 @@ impl Row {
         self.modified
     }
 
+    /// mark the content as changed, which also makes its highlighting stale
     #[inline]
     pub fn set_modified(&mut self) {
         self.modified = true;
+        self.highlighted = false;
     }
 
     #[inline]
@@ -74,6 +88,12 @@ impl Row {
         self.highlighted = false;
     }
 
+    #[must_use]
+    #[inline]
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
     #[must_use]
     #[inline]
     pub fn as_bytes(&self) -> &[u8] {
@@ -82,71 +102,145 @@ impl Row {
 }
 
 impl Row {
+    /// render the screen columns `start..end`, graphemes in `selected` are drawn in reverse video
     #[must_use]
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.content.len());
-        let start = cmp::min(start, end);
+    pub fn render(
+        &self,
+        start: usize,
+        end: usize,
+        selected: Option<Range<usize>>,
+        tab_width: usize,
+    ) -> String {
+        let selected = selected.unwrap_or_default();
         let mut result = String::new();
         let mut result_tmp = String::new();
         let mut current_highlighting = &HighlightType::None;
-        #[allow(clippy::arithmetic_side_effects)]
-        for (index, grapheme) in self
-            .content
-            .graphemes(true)
-            .map(|s| if s == "\t" { " " } else { s })
-            .enumerate()
-            .skip(start)
-            .take(end - start)
-        {
-            if let Some(c) = grapheme.chars().next() {
+        let mut current_selected = false;
+        let mut column: usize = 0;
+        for index in 0..self.len() {
+            if column >= end {
+                break;
+            }
+            let Some(grapheme) = self.grapheme(index) else {
+                break;
+            };
+            let next_column = column.saturating_add(self.grapheme_width(index, column, tab_width));
+            if next_column > start {
                 let highlight_type = self.highlighting.get(index).unwrap_or_default();
-                if highlight_type == current_highlighting {
-                    result_tmp.push(c);
-                } else {
-                    result.push_str(&format!(
-                        "{}",
-                        style::style(&result_tmp).with(current_highlighting.into())
-                    ));
+                let is_selected = selected.contains(&index);
+                if highlight_type != current_highlighting || is_selected != current_selected {
+                    result.push_str(&styled(&result_tmp, current_highlighting, current_selected));
                     result_tmp.clear();
-                    result_tmp.push(c);
                     current_highlighting = highlight_type;
+                    current_selected = is_selected;
+                }
+                if grapheme == "\t" || column < start || next_column > end {
+                    // tabs and wide graphemes cut by the screen edge are drawn as spaces
+                    let visible =
+                        cmp::min(next_column, end).saturating_sub(cmp::max(column, start));
+                    result_tmp.push_str(&" ".repeat(visible));
+                } else {
+                    result_tmp.push_str(grapheme);
                 }
             }
+            column = next_column;
         }
         if !result_tmp.is_empty() {
-            result.push_str(&format!(
-                "{}",
-                style::style(&result_tmp).with(current_highlighting.into())
-            ));
+            result.push_str(&styled(&result_tmp, current_highlighting, current_selected));
         }
         result
     }
 
+    /// Start index of every screen line when the row is wrapped at `width` columns.
+    /// Lines are broken after whitespace when possible, whitespace at a break hangs past
+    /// the width instead of starting a line. The first start is always 0.
+    #[must_use]
+    pub fn wrap_points(&self, width: usize, tab_width: usize) -> Vec<usize> {
+        let mut points = vec![0];
+        if width == 0 {
+            return points;
+        }
+        let (mut start, mut start_column, mut column): (usize, usize, usize) = (0, 0, 0);
+        let mut last_break = None;
+        for index in 0..self.len() {
+            let grapheme_width = self.grapheme_width(index, column, tab_width);
+            let line_width = column
+                .saturating_add(grapheme_width)
+                .saturating_sub(start_column);
+            let is_blank = self.grapheme(index).is_some_and(highlighting::is_blank);
+            if line_width > width && index > start && !is_blank {
+                (start, start_column) = match last_break {
+                    Some((at, at_column)) if at > start => (at, at_column),
+                    _ => (index, column),
+                };
+                points.push(start);
+                last_break = None;
+            }
+            column = column.saturating_add(grapheme_width);
+            if is_blank {
+                last_break = Some((index.saturating_add(1), column));
+            }
+        }
+        points
+    }
+
+    /// screen column at which the grapheme `index` starts
+    #[must_use]
+    pub fn column(&self, index: usize, tab_width: usize) -> usize {
+        (0..cmp::min(index, self.len())).fold(0, |column, i| {
+            column.saturating_add(self.grapheme_width(i, column, tab_width))
+        })
+    }
+
+    /// first screen column at or after `column` where a grapheme starts
+    #[must_use]
+    pub fn boundary_after(&self, column: usize, tab_width: usize) -> usize {
+        let mut current: usize = 0;
+        for index in 0..self.len() {
+            if current >= column {
+                return current;
+            }
+            current = current.saturating_add(self.grapheme_width(index, current, tab_width));
+        }
+        cmp::max(current, column)
+    }
+
+    /// index of the grapheme covering the screen column `column`, the length if it is past the end
+    #[must_use]
+    pub fn index_at_column(&self, column: usize, tab_width: usize) -> usize {
+        let mut current: usize = 0;
+        for index in 0..self.len() {
+            current = current.saturating_add(self.grapheme_width(index, current, tab_width));
+            if current > column {
+                return index;
+            }
+        }
+        self.len()
+    }
+
     #[must_use]
     pub fn split(&mut self, at: usize) -> Row {
         let mut new_row = if at >= self.len() {
             Row::default()
         } else {
-            let (mut current_row, mut new_row) = (String::new(), String::new());
-            let (mut current_len, mut new_len) = (0, 0);
-            for (index, grapheme) in self.content.graphemes(true).enumerate() {
-                if index < at {
-                    current_len += 1;
-                    current_row.push_str(grapheme);
-                } else {
-                    new_len += 1;
-                    new_row.push_str(grapheme);
-                }
-            }
-            self.content = current_row;
-            self.len = current_len;
+            let index = self.byte_index(at);
+            let content = self.content.split_off(index);
+            let graphemes = self
+                .graphemes
+                .split_off(at)
+                .into_iter()
+                .map(|offset| offset.saturating_sub(index))
+                .collect();
             self.set_modified();
             Row {
-                content: new_row,
+                content,
+                graphemes,
                 highlighting: Vec::new(),
-                len: new_len,
                 modified: true,
                 highlighted: false,
+                matched: false,
+                start_state: LineState::default(),
+                end_state: LineState::default(),
             }
         };
         new_row.set_modified();
@@ -154,372 +248,239 @@ impl Row {
     }
 
     pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.content.push(c);
-            self.len += 1;
-        } else {
-            let mut result = String::new();
-            let mut length = 0;
-            for (index, grapheme) in self.content.graphemes(true).enumerate() {
-                length += 1;
-                if index == at {
-                    length += 1;
-                    result.push(c);
-                }
-                result.push_str(grapheme);
-            }
-            self.len = length;
-            self.content = result;
-        }
-        self.set_modified();
+        self.insert_str(at, c.encode_utf8(&mut [0; 4]));
     }
 
     pub fn delete(&mut self, at: usize) {
         if at < self.len() {
-            let mut res: String = String::new();
-            let mut length = 0;
-            for (index, grapheme) in self.content.graphemes(true).enumerate() {
-                if index != at {
-                    length += 1;
-                    res.push_str(grapheme);
-                }
-            }
-            self.content = res;
-            self.len = length;
-            self.set_modified();
+            self.delete_range(at, at.saturating_add(1));
         }
     }
 
+    pub fn insert_str(&mut self, at: usize, s: &str) {
+        let at = cmp::min(at, self.len());
+        let index = self.byte_index(at);
+        let first = at.saturating_sub(1);
+        let last = cmp::min(at.saturating_add(1), self.len());
+        let start = self.byte_index(first);
+        let end = self.byte_index(last).saturating_add(s.len());
+        self.content.insert_str(index, s);
+        self.reindex(first, last, start, end, |offset| {
+            offset.saturating_add(s.len())
+        });
+        self.set_modified();
+    }
+
+    /// remove the graphemes in `start..end` and return them
+    pub fn delete_range(&mut self, start: usize, end: usize) -> String {
+        let end = cmp::min(end, self.len());
+        let start = cmp::min(start, end);
+        let (start_index, end_index) = (self.byte_index(start), self.byte_index(end));
+        let first = start.saturating_sub(1);
+        let last = cmp::min(end.saturating_add(1), self.len());
+        let reindex_start = self.byte_index(first);
+        let reindex_end = self
+            .byte_index(last)
+            .saturating_sub(end_index.saturating_sub(start_index));
+        let removed: String = self.content.drain(start_index..end_index).collect();
+        let removed_len = removed.len();
+        self.reindex(first, last, reindex_start, reindex_end, |offset| {
+            offset.saturating_sub(removed_len)
+        });
+        self.set_modified();
+        removed
+    }
+
     pub fn append(&mut self, row: &Row) {
+        let len = self.len();
+        let offset = self.content.len();
         self.content.push_str(&row.content);
-        self.len += row.len;
+        self.graphemes.extend(
+            row.graphemes
+                .iter()
+                .map(|index| index.saturating_add(offset)),
+        );
+        // the last grapheme of this row may combine with the first one of `row`
+        let first = len.saturating_sub(1);
+        let last = cmp::min(len.saturating_add(1), self.len());
+        let (start, end) = (self.byte_index(first), self.byte_index(last));
+        self.reindex(first, last, start, end, |index| index);
         self.set_modified();
     }
 
-    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
-        if at > self.len() || query.is_empty() {
-            return None;
+    /// grapheme range of the first match of `query` starting at or after `at`, or of the
+    /// last one starting before `at` when searching backward
+    #[must_use]
+    pub fn find(
+        &self,
+        query: &Query,
+        at: usize,
+        direction: SearchDirection,
+    ) -> Option<Range<usize>> {
+        let mut matches = self.matches(query);
+        match direction {
+            SearchDirection::Forward => matches.find(|found| found.start >= at),
+            SearchDirection::Backward => matches.take_while(|found| found.start < at).last(),
         }
+    }
 
-        let (start, end) = match direction {
-            SearchDirection::Forward => (at, self.len()),
-            SearchDirection::Backward => (0, at),
-        };
-        #[allow(clippy::arithmetic_side_effects)]
-        let substring: String = self
-            .content
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-            .collect();
-        let matching_byte_index = match direction {
-            SearchDirection::Forward => substring.find(query),
-            SearchDirection::Backward => substring.rfind(query),
-        };
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in substring.grapheme_indices(true).enumerate() {
-                if matching_byte_index == byte_index {
-                    #[allow(clippy::arithmetic_side_effects)]
-                    return Some(start + grapheme_index);
-                }
-            }
-        }
-        None
+    /// grapheme ranges of all the matches of `query`, a match which starts or ends inside
+    /// a grapheme covers the whole grapheme
+    pub fn matches<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = Range<usize>> + 'a {
+        query.find_iter(&self.content).map(|(start, end)| {
+            let first = self
+                .graphemes
+                .partition_point(|&index| index <= start)
+                .saturating_sub(1);
+            let last = self.graphemes.partition_point(|&index| index < end);
+            first..last
+        })
     }
 
+    /// Highlight the row starting in the lexer state `start` and return the state it
+    /// ends in. The cached highlighting is kept when neither the content nor `start`
+    /// changed and there are no matches to mark or clear.
     pub fn highlight(
         &mut self,
         opts: &HighlightingOptions,
-        word: Option<&String>,
-        start_with_comment: bool,
-    ) -> bool {
-        if self.highlighted && word.is_none() {
-            if let Some(hl_type) = self.highlighting.last() {
-                return *hl_type == HighlightType::MultilineComment
-                    && self.content.len() > 1
-                    && &self.content[self.content.len().saturating_sub(2)..] == "*/";
-            }
+        word: Option<&Query>,
+        start: &LineState,
+    ) -> &LineState {
+        if self.highlighted && !self.matched && word.is_none() && self.start_state == *start {
+            return &self.end_state;
         }
 
-        self.highlighting.clear();
-        let chars: Vec<char> = self.content.chars().collect();
-        let mut index = 0;
-        let mut in_ml_comment = start_with_comment;
-        if in_ml_comment {
-            let closing_index = self
-                .content
-                .find("*/")
-                .map_or(chars.len(), |cl_idx| cl_idx + 2);
-            self.highlighting.append(
-                &mut iter::repeat(HighlightType::MultilineComment)
-                    .take(closing_index)
-                    .collect(),
-            );
-            index = closing_index;
-        }
-
-        while let Some(c) = chars.get(index) {
-            if self.highlight_multiline_comment(&mut index, opts, *c, &chars) {
-                in_ml_comment = true;
-                continue;
-            }
-            in_ml_comment = false;
-            if self.highlight_char(&mut index, opts, *c, &chars)
-                || self.highlight_comment(&mut index, opts, *c, &chars)
-                || self.highlight_primary_keywords(&mut index, opts, &chars)
-                || self.highlight_secondary_keywords(&mut index, opts, &chars)
-                || self.highlight_string(&mut index, opts, *c, &chars)
-                || self.highlight_number(&mut index, opts, *c, &chars)
-            {
-                continue;
-            }
-            self.highlighting.push(HighlightType::None);
-            index += 1;
-        }
+        let (highlighting, end) = highlighting::highlight_line(&self.content, opts, start);
+        self.highlighting = highlighting;
         self.highlight_match(word);
         self.highlighted = true;
+        self.matched = word.is_some();
+        self.start_state = start.clone();
+        self.end_state = end;
+        &self.end_state
+    }
 
-        in_ml_comment && &self.content[self.content.len().saturating_sub(2)..] != "*/"
+    /// lexer state at the end of the row, as of its last highlighting
+    #[must_use]
+    #[inline]
+    pub fn end_state(&self) -> &LineState {
+        &self.end_state
+    }
+
+    /// highlight type of every grapheme
+    #[must_use]
+    pub fn highlighting(&self) -> &[HighlightType] {
+        &self.highlighting
     }
 }
 
 impl Row {
-    fn highlight_match(&mut self, word: Option<&String>) {
-        if let Some(word) = word {
-            if word.is_empty() {
-                return;
-            }
-
-            let mut index = 0;
-            let word_len = word.graphemes(true).count();
-            while let Some(search_match) = self.find(word, index, SearchDirection::Forward) {
-                if let Some(next_index) = search_match.checked_add(word_len) {
-                    for i in search_match..next_index {
-                        self.highlighting[i] = HighlightType::Match;
-                    }
-                    index = next_index;
-                } else {
-                    break;
-                }
+    fn highlight_match(&mut self, word: Option<&Query>) {
+        let Some(word) = word else {
+            return;
+        };
+        let matches: Vec<Range<usize>> = self.matches(word).collect();
+        for found in matches {
+            for hl_type in self
+                .highlighting
+                .iter_mut()
+                .take(found.end)
+                .skip(found.start)
+            {
+                *hl_type = HighlightType::Match;
             }
         }
     }
+}
 
-    fn highlight_char(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.characters() && c == '\'' {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                let closing_index = if *next_char == '\\' {
-                    index.saturating_add(3)
-                } else {
-                    index.saturating_add(2)
-                };
-                if let Some(closing_char) = chars.get(closing_index) {
-                    if *closing_char == '\'' {
-                        for _ in *index..=closing_index {
-                            self.highlighting.push(HighlightType::Character);
-                        }
-                        *index = closing_index + 1;
-                        return true;
-                    }
-                }
-            }
-        }
-        false
+impl Row {
+    /// leading whitespace of the row
+    #[must_use]
+    pub fn indentation(&self) -> &str {
+        let end = self
+            .content
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(self.content.len());
+        self.content.get(..end).unwrap_or_default()
     }
 
-    fn highlight_comment(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '/' {
-                    for _ in *index..chars.len() {
-                        self.highlighting.push(HighlightType::Comment);
-                        *index += 1;
-                    }
-                    return true;
-                }
-            };
-        }
-        false
+    /// content of the graphemes in `start..end`
+    #[must_use]
+    pub fn substring(&self, start: usize, end: usize) -> &str {
+        let end = self.byte_index(end);
+        let start = cmp::min(self.byte_index(start), end);
+        self.content.get(start..end).unwrap_or_default()
     }
 
-    fn highlight_multiline_comment(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '*' {
-                    let closing_index =
-                        if let Some(closing_index) = self.content[*index + 2..].find("*/") {
-                            *index + closing_index + 4
-                        } else {
-                            chars.len()
-                        };
-                    for _ in *index..closing_index {
-                        self.highlighting.push(HighlightType::MultilineComment);
-                        *index += 1;
-                    }
-                    return true;
-                }
-            };
-        }
-        false
+    #[must_use]
+    pub fn grapheme(&self, index: usize) -> Option<&str> {
+        let start = *self.graphemes.get(index)?;
+        let end = self.byte_index(index.saturating_add(1));
+        self.content.get(start..end)
     }
 
-    fn highlight_string(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.strings() && c == '"' {
-            *index += 1;
-            while let Some(next_char) = chars.get(*index) {
-                if *next_char != '"' {
-                    self.highlighting.push(HighlightType::String);
-                    *index += 1;
-                } else {
-                    break;
-                }
+    /// number of terminal columns the grapheme at `index` takes when it starts at `column`,
+    /// a tab reaches the next tab stop, wide characters take two columns
+    fn grapheme_width(&self, index: usize, column: usize, tab_width: usize) -> usize {
+        match self.grapheme(index) {
+            Some("\t") => {
+                let tab_width = tab_width.max(1);
+                tab_width.saturating_sub(column % tab_width)
             }
-            self.highlighting.push(HighlightType::String); // current char
-            self.highlighting.push(HighlightType::String); // next char: '"'
-            *index += 1;
-            return true;
+            Some(grapheme) => grapheme.width(),
+            None => 0,
         }
-        false
     }
 
-    fn highlight_number(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        c: char,
-        chars: &[char],
-    ) -> bool {
-        if opts.numbers() && c.is_ascii_digit() {
-            if *index != 0 {
-                let prev_char: char = chars[*index - 1];
-                if !is_separator(prev_char) {
-                    return false;
-                }
-            }
-            *index += 1;
-            while let Some(next_char) = chars.get(*index) {
-                if next_char.is_ascii_digit() || *next_char == '.' {
-                    self.highlighting.push(HighlightType::Number);
-                    *index += 1;
-                } else {
-                    break;
-                }
-            }
-            self.highlighting.push(HighlightType::Number);
-            return true;
-        }
-        false
+    /// byte offset of the grapheme at `at`, the content length if `at` is past the end
+    fn byte_index(&self, at: usize) -> usize {
+        self.graphemes
+            .get(at)
+            .copied()
+            .unwrap_or(self.content.len())
     }
 
-    fn hightlight_str(
-        &mut self,
-        index: &mut usize,
-        substring: &str,
-        chars: &[char],
-        hl_type: &HighlightType,
-    ) -> bool {
-        if substring.is_empty() {
-            return false;
-        }
-
-        for (substr_index, c) in substring.chars().enumerate() {
-            if let Some(next_char) = chars.get(*index + substr_index) {
-                if *next_char != c {
-                    return false;
-                }
-            } else {
-                return false;
-            }
+    /// Re-segment the bytes `start..end` of the content, which replace the graphemes
+    /// `first..last` of the index. Offsets of the following graphemes are mapped by `shift`.
+    fn reindex<F>(&mut self, first: usize, last: usize, start: usize, end: usize, shift: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        let tail: Vec<usize> = self
+            .graphemes
+            .drain(first..)
+            .skip(last.saturating_sub(first))
+            .map(shift)
+            .collect();
+        if let Some(segment) = self.content.get(start..end) {
+            self.graphemes.extend(
+                segment
+                    .grapheme_indices(true)
+                    .map(|(index, _)| index.saturating_add(start)),
+            );
         }
-        self.highlighting.append(
-            &mut iter::repeat(hl_type.clone())
-                .take(substring.len())
-                .collect(),
-        );
-        *index += substring.len();
-        true
+        self.graphemes.extend(tail);
     }
+}
 
-    fn highlight_keywords(
-        &mut self,
-        index: &mut usize,
-        chars: &[char],
-        keywords: &[&'static str],
-        hl_type: &HighlightType,
-    ) -> bool {
-        if *index > 0 {
-            let prev_char = chars[*index - 1];
-            if !is_separator(prev_char) {
-                return false;
-            }
-        }
-        for word in keywords.iter() {
-            let next_index = *index + word.len();
-            if let Some(&next_char) = chars.get(next_index) {
-                if !is_separator(next_char) {
-                    continue;
-                }
-            }
-            if self.hightlight_str(index, word, chars, hl_type) {
-                return true;
-            }
-        }
-        false
+fn styled(text: &str, highlight_type: &HighlightType, selected: bool) -> String {
+    let content = style::style(text).with(highlight_type.into());
+    if selected {
+        format!("{}", content.reverse())
+    } else {
+        format!("{}", content)
     }
+}
 
-    fn highlight_primary_keywords(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        chars: &[char],
-    ) -> bool {
-        self.highlight_keywords(
-            index,
-            chars,
-            opts.primary_keywords(),
-            &HighlightType::PrimaryKeywords,
-        )
-    }
+#[cfg(test)]
+mod tests {
+    use super::Row;
 
-    fn highlight_secondary_keywords(
-        &mut self,
-        index: &mut usize,
-        opts: &HighlightingOptions,
-        chars: &[char],
-    ) -> bool {
-        self.highlight_keywords(
-            index,
-            chars,
-            opts.secondary_keywords(),
-            &HighlightType::SecondaryKeywords,
-        )
+    #[test]
+    fn whitespace_hangs_at_a_wrap() {
+        assert_eq!(Row::from("aaaa bbbb").wrap_points(4, 4), [0, 5]);
+        assert_eq!(Row::from("aa bb cc").wrap_points(5, 4), [0, 6]);
+        assert_eq!(Row::from("aaaa   bbbb").wrap_points(4, 4), [0, 7]);
+        assert_eq!(Row::from("aaaaaaaaa").wrap_points(4, 4), [0, 4, 8]);
     }
 }
-
-fn is_separator(c: char) -> bool {
-    c.is_ascii_punctuation() || c.is_whitespace()
-}
@@ -0,0 +1,111 @@
+use std::fmt::{self, Display};
+
+use regex::{Regex, RegexBuilder};
+
+/// Flags changing how the text typed in the search prompt is matched.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    regex: bool,
+    case_insensitive: bool,
+    whole_word: bool,
+}
+
+impl SearchOptions {
+    #[must_use]
+    #[inline]
+    pub fn regex(&self) -> bool {
+        self.regex
+    }
+
+    #[inline]
+    pub fn toggle_regex(&mut self) {
+        self.regex = !self.regex;
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    #[inline]
+    pub fn toggle_case_insensitive(&mut self) {
+        self.case_insensitive = !self.case_insensitive;
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn whole_word(&self) -> bool {
+        self.whole_word
+    }
+
+    #[inline]
+    pub fn toggle_whole_word(&mut self) {
+        self.whole_word = !self.whole_word;
+    }
+}
+
+/// the active flags in brackets, nothing when every flag is off
+impl Display for SearchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags: Vec<&str> = [
+            (self.regex, "regex"),
+            (self.case_insensitive, "ignore case"),
+            (self.whole_word, "whole word"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect();
+        if flags.is_empty() {
+            Ok(())
+        } else {
+            write!(f, " [{}]", flags.join(", "))
+        }
+    }
+}
+
+/// A search pattern compiled with its options. Literal patterns are escaped, so
+/// every mode is matched by the same regex engine.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Query {
+    /// # Errors
+    ///
+    /// fails if the pattern is not a valid regex in regex mode
+    pub fn new(pattern: &str, options: SearchOptions) -> Result<Self, regex::Error> {
+        let mut source = if options.regex() {
+            format!("(?:{})", pattern)
+        } else {
+            regex::escape(pattern)
+        };
+        if options.whole_word() {
+            source = format!(r"\b{}\b", source);
+        }
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(options.case_insensitive())
+            .build()?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// byte ranges of the non empty matches in `text`
+    pub fn find_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.regex
+            .find_iter(text)
+            .filter(|found| !found.is_empty())
+            .map(|found| (found.start(), found.end()))
+    }
+}
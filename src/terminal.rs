@@ -3,7 +3,7 @@ use std::io::{self, Write};
 use crossterm::{
     cursor,
     event::{self, Event, KeyEvent, KeyEventKind},
-    terminal,
+    execute, terminal,
 };
 
 use crate::editor::Position;
@@ -50,6 +50,13 @@ impl Terminal {
         print!("{}", terminal::Clear(terminal::ClearType::CurrentLine));
     }
 
+    /// copy `text` to the system clipboard with an OSC 52 escape sequence,
+    /// which also works over SSH when the terminal supports it
+    #[inline]
+    pub fn set_clipboard(text: &str) {
+        print!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()));
+    }
+
     /// # Errors
     #[inline]
     pub fn flush() -> Result<(), io::Error> {
@@ -60,10 +67,21 @@ impl Terminal {
     #[inline]
     pub fn read_key() -> Result<KeyEvent, io::Error> {
         loop {
-            if let Some(Event::Key(key)) = event::read().into_iter().next() {
-                if key.kind == KeyEventKind::Press {
-                    return Ok(key);
-                }
+            if let Event::Key(key) = Terminal::read_event()? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// read the next key press or bracketed paste
+    /// # Errors
+    #[inline]
+    pub fn read_event() -> Result<Event, io::Error> {
+        loop {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => return Ok(Event::Key(key)),
+                Event::Paste(text) => return Ok(Event::Paste(text)),
+                _ => (),
             }
         }
     }
@@ -95,7 +113,9 @@ impl Default for Terminal {
     #[allow(clippy::expect_used)]
     fn default() -> Self {
         // let (width, height) = terminal::size().expect("fail to get terminal size");
-        // terminal::enable_raw_mode().expect("fail to enable raw mode");
+        terminal::enable_raw_mode().expect("fail to enable raw mode");
+        execute!(io::stdout(), event::EnableBracketedPaste)
+            .expect("fail to enable bracketed paste");
         Terminal {
             // size: Size::new(width, height.saturating_sub(2)),
         }
@@ -106,6 +126,35 @@ impl Drop for Terminal {
     #[inline]
     #[allow(clippy::expect_used)]
     fn drop(&mut self) {
+        execute!(io::stdout(), event::DisableBracketedPaste)
+            .expect("fail to disable bracketed paste");
         terminal::disable_raw_mode().expect("fail to disable raw mode");
     }
 }
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(bytes.len().div_ceil(3).saturating_mul(4));
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk.first().copied().unwrap_or_default(),
+            chunk.get(1).copied().unwrap_or_default(),
+            chunk.get(2).copied().unwrap_or_default(),
+        ];
+        let indices = [
+            b[0] >> 2,
+            (b[0] & 0x03) << 4 | b[1] >> 4,
+            (b[1] & 0x0f) << 2 | b[2] >> 6,
+            b[2] & 0x3f,
+        ];
+        // a chunk of n bytes is encoded by n + 1 characters and padded to 4
+        for (i, index) in indices.iter().enumerate() {
+            if i <= chunk.len() {
+                result.push(char::from(ALPHABET[usize::from(*index)]));
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}